@@ -0,0 +1,340 @@
+use serde::{de::Error as _, Deserialize, Deserializer};
+use serde_json::Value;
+
+use crate::error::CompileError;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct SourceLocation {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Program {
+    pub body: Vec<Stmt>,
+    pub loc: Option<SourceLocation>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub enum Stmt {
+    VariableDeclaration(VariableDeclaration),
+    FunctionDeclaration(FunctionDeclaration),
+    ExpressionStatement(ExpressionStatement),
+    BlockStatement(BlockStatement),
+    IfStatement(IfStatement),
+    ReturnStatement(ReturnStatement),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VariableDeclaration {
+    // Source only allows a single declarator per declaration.
+    pub declarations: [VariableDeclarator; 1],
+    pub loc: Option<SourceLocation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct VariableDeclarator {
+    pub id: Identifier,
+    pub init: Expr,
+    pub loc: Option<SourceLocation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FunctionDeclaration {
+    pub id: Identifier,
+    pub params: Vec<Identifier>,
+    pub body: FunctionBody,
+    pub loc: Option<SourceLocation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ExpressionStatement {
+    pub expression: Expr,
+    pub loc: Option<SourceLocation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BlockStatement {
+    pub body: Vec<Stmt>,
+    pub loc: Option<SourceLocation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IfStatement {
+    pub test: Expr,
+    pub consequent: Box<Stmt>,
+    pub alternate: Box<Stmt>,
+    pub loc: Option<SourceLocation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReturnStatement {
+    pub argument: Expr,
+    pub loc: Option<SourceLocation>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub enum Expr {
+    Identifier(Identifier),
+    Literal(Literal),
+    UnaryExpression(UnaryExpression),
+    BinaryExpression(BinaryExpression),
+    LogicalExpression(LogicalExpression),
+    CallExpression(CallExpression),
+    ConditionalExpression(ConditionalExpression),
+    ArrowFunctionExpression(ArrowFunctionExpression),
+    AssignmentExpression(AssignmentExpression),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Identifier {
+    pub name: String,
+    pub loc: Option<SourceLocation>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(from = "EstreeLiteral")]
+pub struct Literal {
+    pub value: LiteralValue,
+    pub loc: Option<SourceLocation>,
+}
+
+#[derive(Deserialize)]
+struct EstreeLiteral {
+    value: LiteralValue,
+    raw: Option<String>,
+    loc: Option<SourceLocation>,
+}
+
+impl From<EstreeLiteral> for Literal {
+    fn from(literal: EstreeLiteral) -> Self {
+        // JSON.stringify turns Infinity into null, so only the raw text tells
+        // a number literal too large for a number like 1e400 apart from null
+        let value = match (literal.value, literal.raw) {
+            (LiteralValue::Null, Some(raw)) if raw != "null" => {
+                raw.parse().map_or(LiteralValue::Null, LiteralValue::Number)
+            }
+            (value, _) => value,
+        };
+
+        Literal {
+            value,
+            loc: literal.loc,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum LiteralValue {
+    Null,
+    Boolean(bool),
+    Number(f64),
+    String(String),
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UnaryExpression {
+    pub operator: UnaryOperator,
+    pub argument: Box<Expr>,
+    pub loc: Option<SourceLocation>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum UnaryOperator {
+    #[serde(rename = "!")]
+    Not,
+    #[serde(rename = "-")]
+    Minus,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BinaryExpression {
+    pub operator: BinaryOperator,
+    pub left: Box<Expr>,
+    pub right: Box<Expr>,
+    pub loc: Option<SourceLocation>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum BinaryOperator {
+    #[serde(rename = "+")]
+    Plus,
+    #[serde(rename = "-")]
+    Minus,
+    #[serde(rename = "*")]
+    Times,
+    #[serde(rename = "/")]
+    Divide,
+    #[serde(rename = "%")]
+    Modulo,
+    #[serde(rename = "<")]
+    Less,
+    #[serde(rename = ">")]
+    Greater,
+    #[serde(rename = "<=")]
+    LessEqual,
+    #[serde(rename = ">=")]
+    GreaterEqual,
+    #[serde(rename = "===")]
+    StrictEqual,
+    #[serde(rename = "!==")]
+    StrictNotEqual,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogicalExpression {
+    pub operator: LogicalOperator,
+    pub left: Box<Expr>,
+    pub right: Box<Expr>,
+    pub loc: Option<SourceLocation>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub enum LogicalOperator {
+    #[serde(rename = "&&")]
+    And,
+    #[serde(rename = "||")]
+    Or,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CallExpression {
+    pub callee: Box<Expr>,
+    pub arguments: Vec<Expr>,
+    pub loc: Option<SourceLocation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConditionalExpression {
+    pub test: Box<Expr>,
+    pub consequent: Box<Expr>,
+    pub alternate: Box<Expr>,
+    pub loc: Option<SourceLocation>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ArrowFunctionExpression {
+    pub params: Vec<Identifier>,
+    pub body: FunctionBody,
+    pub loc: Option<SourceLocation>,
+}
+
+/// Every name in Source §2 is constant, so assignments only get as far as the
+/// checker, which rejects them.
+#[derive(Debug, Deserialize)]
+pub struct AssignmentExpression {
+    pub left: Identifier,
+    pub right: Box<Expr>,
+    pub loc: Option<SourceLocation>,
+}
+
+/// Function declarations always have a block body, arrow functions may have
+/// either a block or a single expression.
+#[derive(Debug)]
+pub enum FunctionBody {
+    Block(BlockStatement),
+    Expression(Box<Expr>),
+}
+
+impl<'de> Deserialize<'de> for FunctionBody {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Value::deserialize(deserializer)?;
+        if value.get("type").and_then(Value::as_str) == Some("BlockStatement") {
+            serde_json::from_value(value)
+                .map(FunctionBody::Block)
+                .map_err(D::Error::custom)
+        } else {
+            serde_json::from_value(value)
+                .map(|expr| FunctionBody::Expression(Box::new(expr)))
+                .map_err(D::Error::custom)
+        }
+    }
+}
+
+const NODE_TYPES: &[&str] = &[
+    "Program",
+    "VariableDeclaration",
+    "VariableDeclarator",
+    "FunctionDeclaration",
+    "ExpressionStatement",
+    "BlockStatement",
+    "IfStatement",
+    "ReturnStatement",
+    "Identifier",
+    "Literal",
+    "UnaryExpression",
+    "BinaryExpression",
+    "LogicalExpression",
+    "CallExpression",
+    "ConditionalExpression",
+    "ArrowFunctionExpression",
+    "AssignmentExpression",
+];
+
+const OPERATORS: &[&str] = &[
+    "!", "-", "+", "*", "/", "%", "<", ">", "<=", ">=", "===", "!==", "&&", "||", "=",
+];
+
+/// Deserializes a program from its ESTree JSON. When that fails because of
+/// nodes or operators outside of Source §2, the errors point out each of them
+/// rather than being serde's.
+pub(crate) fn from_estree(value: Value) -> Result<Program, Vec<CompileError>> {
+    Program::deserialize(&value).map_err(|err| {
+        let mut errors = vec![];
+        find_unsupported(&value, &mut errors);
+        // the fields of a node are visited in alphabetical order
+        errors.sort_by_key(|err| err.loc().map(|loc| (loc.start.line, loc.start.column)));
+        if errors.is_empty() {
+            errors.push(CompileError::InvalidEstree(err));
+        }
+        errors
+    })
+}
+
+fn find_unsupported(value: &Value, errors: &mut Vec<CompileError>) {
+    match value {
+        Value::Array(values) => values
+            .iter()
+            .for_each(|value| find_unsupported(value, errors)),
+        Value::Object(object) => {
+            let loc = || {
+                object
+                    .get("loc")
+                    .and_then(|loc| SourceLocation::deserialize(loc).ok())
+            };
+
+            if let Some(kind) = object.get("type").and_then(Value::as_str) {
+                if !NODE_TYPES.contains(&kind) {
+                    // the node's children are whatever its kind has
+                    errors.push(CompileError::UnsupportedNode {
+                        kind: kind.into(),
+                        loc: loc(),
+                    });
+                    return;
+                }
+            }
+            if let Some(operator) = object.get("operator").and_then(Value::as_str) {
+                if !OPERATORS.contains(&operator) {
+                    errors.push(CompileError::UnknownOperator {
+                        operator: operator.into(),
+                        loc: loc(),
+                    });
+                }
+            }
+
+            object
+                .values()
+                .for_each(|value| find_unsupported(value, errors));
+        }
+        _ => {}
+    }
+}
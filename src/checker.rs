@@ -0,0 +1,228 @@
+//! Static checks run on a program before any code is generated for it, so that
+//! programs the code generator can't handle are rejected with their location
+//! instead of failing halfway through building the module. The checks keep
+//! going past an error, so that every error in the program is reported at once.
+
+use std::collections::HashMap;
+
+use crate::ast::{Expr, FunctionBody, Identifier, Program, SourceLocation, Stmt};
+use crate::error::CompileError;
+
+/// The functions built into the code generator rather than declared, with the
+/// number of arguments they take if it is fixed. Calls to them are inlined,
+/// and used as values they are closures like any other function. Declaring a
+/// name shadows the built-in function of that name.
+const BUILTINS: &[(&str, Option<usize>)] = &[
+    ("display", Some(1)),
+    ("pair", Some(2)),
+    ("head", Some(1)),
+    ("tail", Some(1)),
+    ("set_head", Some(2)),
+    ("set_tail", Some(2)),
+    ("is_pair", Some(1)),
+    ("is_null", Some(1)),
+    ("list", None),
+    ("stringify", Some(1)),
+];
+
+/// Returns whether `name` is a built-in function, rather than any other name
+/// the compiled code looks up in its frames.
+pub(crate) fn is_builtin(name: &str) -> bool {
+    find_builtin(name).is_some()
+}
+
+/// Returns the built-in function called `name` with its arity, if there is
+/// one.
+pub(crate) fn find_builtin(name: &str) -> Option<&'static (&'static str, Option<usize>)> {
+    BUILTINS.iter().find(|(builtin, _)| *builtin == name)
+}
+
+/// The names declared in a function or block with where they are declared,
+/// like an `Env` without offsets.
+struct Scope<'a> {
+    names: HashMap<&'a str, Option<SourceLocation>>,
+    parent: Option<&'a Scope<'a>>,
+}
+
+impl<'a> Scope<'a> {
+    /// Returns where the name is declared, if it is.
+    fn lookup(&self, name: &str) -> Option<Option<SourceLocation>> {
+        match self.names.get(name) {
+            Some(loc) => Some(*loc),
+            None => self.parent.and_then(|parent| parent.lookup(name)),
+        }
+    }
+}
+
+/// Returns the errors in the program in the order of the constructs they are
+/// about. The declarations of the prelude are in scope in the program, but are
+/// not checked themselves.
+pub(crate) fn check(program: &Program, prelude: &Program) -> Result<(), Vec<CompileError>> {
+    let mut checker = Checker { errors: vec![] };
+    let prelude_scope = checker.scope(&[], &prelude.body, None);
+    let scope = checker.scope(&[], &program.body, Some(&prelude_scope));
+    checker.stmts(&program.body, &scope, false);
+
+    if checker.errors.is_empty() {
+        Ok(())
+    } else {
+        Err(checker.errors)
+    }
+}
+
+struct Checker {
+    errors: Vec<CompileError>,
+}
+
+impl Checker {
+    /// Declares the parameters and the declarations of the body, which are in
+    /// scope throughout it.
+    fn scope<'a>(
+        &mut self,
+        params: &'a [Identifier],
+        body: &'a [Stmt],
+        parent: Option<&'a Scope<'a>>,
+    ) -> Scope<'a> {
+        let decls = body.iter().filter_map(|stmt| match stmt {
+            Stmt::VariableDeclaration(decl) => Some(&decl.declarations[0].id),
+            Stmt::FunctionDeclaration(decl) => Some(&decl.id),
+            _ => None,
+        });
+
+        let mut names = HashMap::new();
+        for id in params.iter().chain(decls) {
+            match names.get(id.name.as_str()) {
+                Some(previous) => self.errors.push(CompileError::DuplicateDeclaration {
+                    name: id.name.clone(),
+                    loc: id.loc,
+                    previous: *previous,
+                }),
+                None => {
+                    names.insert(id.name.as_str(), id.loc);
+                }
+            }
+        }
+
+        Scope { names, parent }
+    }
+
+    fn stmts(&mut self, stmts: &[Stmt], scope: &Scope, in_function: bool) {
+        for stmt in stmts {
+            self.stmt(stmt, scope, in_function);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt, scope: &Scope, in_function: bool) {
+        match stmt {
+            Stmt::VariableDeclaration(decl) => self.expr(&decl.declarations[0].init, scope),
+            Stmt::FunctionDeclaration(decl) => self.function(&decl.params, &decl.body, scope),
+            Stmt::ExpressionStatement(stmt) => self.expr(&stmt.expression, scope),
+            Stmt::BlockStatement(block) => {
+                let scope = self.scope(&[], &block.body, Some(scope));
+                self.stmts(&block.body, &scope, in_function);
+            }
+            Stmt::IfStatement(stmt) => {
+                self.expr(&stmt.test, scope);
+                self.stmt(&stmt.consequent, scope, in_function);
+                self.stmt(&stmt.alternate, scope, in_function);
+            }
+            Stmt::ReturnStatement(stmt) => {
+                if !in_function {
+                    self.errors
+                        .push(CompileError::ReturnOutsideFunction { loc: stmt.loc });
+                }
+                self.expr(&stmt.argument, scope);
+            }
+        }
+    }
+
+    /// A function's parameters and the declarations of its body share a scope,
+    /// so a declaration may not redeclare a parameter.
+    fn function(&mut self, params: &[Identifier], body: &FunctionBody, parent: &Scope) {
+        match body {
+            FunctionBody::Block(block) => {
+                let scope = self.scope(params, &block.body, Some(parent));
+                self.stmts(&block.body, &scope, true);
+            }
+            FunctionBody::Expression(expr) => {
+                let scope = self.scope(params, &[], Some(parent));
+                self.expr(expr, &scope);
+            }
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr, scope: &Scope) {
+        match expr {
+            Expr::Identifier(id) => self.name(id, scope),
+            Expr::Literal(_) => {}
+            Expr::UnaryExpression(expr) => self.expr(&expr.argument, scope),
+            Expr::BinaryExpression(expr) => {
+                self.expr(&expr.left, scope);
+                self.expr(&expr.right, scope);
+            }
+            Expr::LogicalExpression(expr) => {
+                self.expr(&expr.left, scope);
+                self.expr(&expr.right, scope);
+            }
+            Expr::CallExpression(expr) => {
+                match &*expr.callee {
+                    Expr::Identifier(callee) if scope.lookup(&callee.name).is_none() => {
+                        match find_builtin(&callee.name) {
+                            Some((name, Some(expected))) if *expected != expr.arguments.len() => {
+                                self.errors.push(CompileError::BuiltinArity {
+                                    name: name.to_string(),
+                                    expected: *expected,
+                                    count: expr.arguments.len(),
+                                    loc: expr.loc,
+                                })
+                            }
+                            Some(_) => {}
+                            None => self.name(callee, scope),
+                        }
+                    }
+                    callee => self.expr(callee, scope),
+                }
+                for arg in expr.arguments.iter() {
+                    self.expr(arg, scope);
+                }
+            }
+            Expr::ConditionalExpression(expr) => {
+                self.expr(&expr.test, scope);
+                self.expr(&expr.consequent, scope);
+                self.expr(&expr.alternate, scope);
+            }
+            Expr::ArrowFunctionExpression(expr) => self.function(&expr.params, &expr.body, scope),
+            Expr::AssignmentExpression(expr) => {
+                let name = &expr.left.name;
+                match scope.lookup(name) {
+                    Some(declared) => self.errors.push(CompileError::AssignmentToConstant {
+                        name: name.clone(),
+                        loc: expr.loc,
+                        declared,
+                    }),
+                    // built-ins and undefined are constants without a declaration
+                    None if is_builtin(name) || name == "undefined" => {
+                        self.errors.push(CompileError::AssignmentToConstant {
+                            name: name.clone(),
+                            loc: expr.loc,
+                            declared: None,
+                        })
+                    }
+                    None => self.name(&expr.left, scope),
+                }
+                self.expr(&expr.right, scope);
+            }
+        }
+    }
+
+    fn name(&mut self, id: &Identifier, scope: &Scope) {
+        if id.name == "undefined" || is_builtin(&id.name) || scope.lookup(&id.name).is_some() {
+            return;
+        }
+
+        self.errors.push(CompileError::UndeclaredName {
+            name: id.name.clone(),
+            loc: id.loc,
+        });
+    }
+}
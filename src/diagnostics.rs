@@ -0,0 +1,269 @@
+//! Renders compile errors as diagnostics, either for a terminal as snippets of
+//! the program with the parts they are about underlined, or as JSON for
+//! editors.
+
+use std::str::FromStr;
+
+use anyhow::{anyhow, Error};
+use serde_json::{json, Value};
+
+use crate::ast::SourceLocation;
+use crate::error::CompileError;
+
+/// A part of the program a diagnostic points at, with what to say about it.
+#[derive(Debug, Clone)]
+pub struct Label {
+    pub loc: SourceLocation,
+    pub message: String,
+    /// Whether the diagnostic is about this part, rather than it being context
+    /// such as where a name is declared.
+    pub primary: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+/// The formats diagnostics can be rendered in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorFormat {
+    Human,
+    Json,
+}
+
+impl FromStr for ErrorFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(ErrorFormat::Human),
+            "json" => Ok(ErrorFormat::Json),
+            _ => Err(anyhow!(
+                "Unknown error format {}, expected human or json",
+                s
+            )),
+        }
+    }
+}
+
+impl From<&CompileError> for Diagnostic {
+    fn from(err: &CompileError) -> Self {
+        let label = |loc: Option<SourceLocation>, message: &str, primary| {
+            loc.map(|loc| Label {
+                loc,
+                message: message.into(),
+                primary,
+            })
+        };
+
+        let (labels, notes) = match err {
+            CompileError::Syntax { .. } => (vec![label(err.loc(), "", true)], vec![]),
+            CompileError::UnsupportedNode { loc, .. }
+            | CompileError::UnknownOperator { loc, .. } => {
+                (vec![label(*loc, "not part of Source §2", true)], vec![])
+            }
+            CompileError::UndeclaredName { loc, .. } => (
+                vec![label(*loc, "not declared", true)],
+                vec!["Names are declared with const or function, or as parameters.".into()],
+            ),
+            CompileError::DuplicateDeclaration { loc, previous, .. } => (
+                vec![
+                    label(*previous, "first declared here", false),
+                    label(*loc, "declared again here", true),
+                ],
+                vec!["A block can declare a name only once, and a function's body can't redeclare its parameters.".into()],
+            ),
+            CompileError::AssignmentToConstant { loc, declared, .. } => (
+                vec![
+                    label(*declared, "declared here", false),
+                    label(*loc, "assigned here", true),
+                ],
+                vec!["All names in Source §2 are constant, so declare a new name for the new value instead.".into()],
+            ),
+            CompileError::ReturnOutsideFunction { loc } => (
+                vec![label(*loc, "not in a function", true)],
+                vec!["The value of a program is the value of its last statement, without a return.".into()],
+            ),
+            CompileError::BuiltinArity {
+                name,
+                expected,
+                count,
+                loc,
+            } => (
+                vec![label(
+                    *loc,
+                    &format!("called here with {} arguments", count),
+                    true,
+                )],
+                vec![format!("{} takes {} arguments.", name, expected)],
+            ),
+            _ => (vec![], vec![]),
+        };
+
+        Diagnostic {
+            message: err.message(),
+            labels: labels.into_iter().flatten().collect(),
+            notes,
+        }
+    }
+}
+
+impl Diagnostic {
+    /// Returns the location of the part of the program the diagnostic is
+    /// about, if any.
+    pub fn loc(&self) -> Option<SourceLocation> {
+        self.labels
+            .iter()
+            .find(|label| label.primary)
+            .map(|label| label.loc)
+    }
+}
+
+/// Renders the diagnostics in the format. `name` is what to call the program
+/// in the human format, and snippets of it are only shown if its `source` text
+/// is known, e.g. not when compiling from ESTree.
+pub fn render(
+    diagnostics: &[Diagnostic],
+    format: ErrorFormat,
+    name: &str,
+    source: Option<&str>,
+) -> String {
+    match format {
+        ErrorFormat::Human => diagnostics
+            .iter()
+            .map(|diagnostic| render_human(diagnostic, name, source))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ErrorFormat::Json => {
+            let diagnostics: Vec<Value> = diagnostics.iter().map(render_json).collect();
+            Value::Array(diagnostics).to_string()
+        }
+    }
+}
+
+/// Renders a diagnostic like rustc does, e.g.
+///
+/// ```text
+/// error: Name x has already been declared.
+///  --> main.js:2:7
+///   |
+/// 1 | const x = 1;
+///   |       - first declared here
+/// 2 | const x = 2;
+///   |       ^ declared again here
+///   |
+///   = note: A block can declare a name only once, ...
+/// ```
+fn render_human(diagnostic: &Diagnostic, name: &str, source: Option<&str>) -> String {
+    let mut out = format!("error: {}\n", diagnostic.message);
+
+    let mut labels: Vec<&Label> = diagnostic.labels.iter().collect();
+    labels.sort_by_key(|label| (label.loc.start.line, label.loc.start.column));
+    let width = labels
+        .iter()
+        .map(|label| label.loc.start.line.to_string().len())
+        .max()
+        .unwrap_or(1);
+    let gutter = " ".repeat(width);
+
+    // columns are 0-based in ESTree, but 1-based in editors
+    if let Some(loc) = diagnostic.loc() {
+        out += &format!(
+            "{}--> {}:{}:{}\n",
+            gutter,
+            name,
+            loc.start.line,
+            loc.start.column + 1
+        );
+    }
+
+    let lines: Vec<&str> = source.map_or(vec![], |source| source.lines().collect());
+    let mut snippet = String::new();
+    let mut last_line = None;
+    for label in labels.iter() {
+        let line_number = label.loc.start.line;
+        let line = match line_number.checked_sub(1).and_then(|i| lines.get(i)) {
+            Some(line) => line,
+            None => continue,
+        };
+        if last_line != Some(line_number) {
+            if last_line.map_or(false, |last| line_number > last + 1) {
+                snippet += "...\n";
+            }
+            snippet += &format!("{:>width$} | {}\n", line_number, line, width = width);
+            last_line = Some(line_number);
+        }
+
+        // keep tabs so that the underline lines up with the code
+        let indent: String = line
+            .chars()
+            .take(label.loc.start.column)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let end_column = if label.loc.end.line == line_number {
+            label.loc.end.column
+        } else {
+            line.chars().count()
+        };
+        let length = end_column.saturating_sub(label.loc.start.column).max(1);
+        let marker = if label.primary { "^" } else { "-" };
+        snippet += format!(
+            "{} | {}{} {}",
+            gutter,
+            indent,
+            marker.repeat(length),
+            label.message
+        )
+        .trim_end();
+        snippet += "\n";
+    }
+
+    if !snippet.is_empty() {
+        out += &format!("{} |\n{}", gutter, snippet);
+        if !diagnostic.notes.is_empty() {
+            out += &format!("{} |\n", gutter);
+        }
+    } else {
+        // without the source, the context goes into notes
+        for label in labels.iter().filter(|label| !label.primary) {
+            out += &format!(
+                "{} = note: {} at {}:{}:{}\n",
+                gutter,
+                label.message,
+                name,
+                label.loc.start.line,
+                label.loc.start.column + 1
+            );
+        }
+    }
+
+    for note in diagnostic.notes.iter() {
+        out += &format!("{} = note: {}\n", gutter, note);
+    }
+
+    out
+}
+
+fn render_json(diagnostic: &Diagnostic) -> Value {
+    let loc = |loc: SourceLocation| {
+        json!({
+            "start": { "line": loc.start.line, "column": loc.start.column },
+            "end": { "line": loc.end.line, "column": loc.end.column },
+        })
+    };
+
+    json!({
+        "severity": "error",
+        "message": diagnostic.message,
+        "loc": diagnostic.loc().map(loc),
+        "labels": diagnostic.labels.iter().map(|label| json!({
+            "message": label.message,
+            "primary": label.primary,
+            "loc": loc(label.loc),
+        })).collect::<Vec<_>>(),
+        "notes": diagnostic.notes,
+    })
+}
@@ -1,6 +1,6 @@
-use anyhow::{anyhow, Error};
+use crate::ast::Stmt;
+use crate::error::CompileError;
 use inkwell::values::PointerValue;
-use serde_json::Value;
 
 use std::{collections::HashMap, rc::Rc};
 
@@ -26,13 +26,17 @@ impl<'ctx> Env<'ctx> {
         self.names.insert(name, self.counter);
     }
 
-    pub fn lookup(&self, name: &str) -> Result<(usize, u64), Error> {
+    pub fn lookup(&self, name: &str) -> Result<(usize, u64), CompileError> {
         if let Some(&offset) = self.names.get(name) {
             return Ok((0, offset));
         }
 
         let mut jumps = 1;
-        let mut frame = self.parent.clone().unwrap();
+        let undeclared = || CompileError::UndeclaredName {
+            name: name.into(),
+            loc: None,
+        };
+        let mut frame = self.parent.clone().ok_or_else(undeclared)?;
 
         loop {
             if let Some(&offset) = frame.names.get(name) {
@@ -41,47 +45,27 @@ impl<'ctx> Env<'ctx> {
                 frame = parent;
                 jumps += 1;
             } else {
-                break Err(anyhow!(format!("Cannot find name {}", name)));
+                break Err(undeclared());
             }
         }
     }
 
-    pub fn add_and_count_decls(&mut self, body: &[Value]) -> Result<u64, Error> {
+    pub fn add_and_count_decls(&mut self, body: &[Stmt]) -> Result<u64, CompileError> {
         let mut count = 0;
 
-        body.iter().for_each(
-            |es_node| match es_node.get("type").unwrap().as_str().unwrap() {
-                "VariableDeclaration" => {
+        for stmt in body {
+            match stmt {
+                Stmt::VariableDeclaration(decl) => {
                     count += 1;
-                    let name = es_node
-                        .get("declarations")
-                        .unwrap()
-                        .as_array()
-                        .unwrap()
-                        .get(0)
-                        .unwrap()
-                        .get("id")
-                        .unwrap()
-                        .get("name")
-                        .unwrap()
-                        .as_str()
-                        .unwrap();
-                    self.add_name(name.into());
+                    self.add_name(decl.declarations[0].id.name.clone());
                 }
-                "FunctionDeclaration" => {
+                Stmt::FunctionDeclaration(decl) => {
                     count += 1;
-                    let name = es_node
-                        .get("id")
-                        .unwrap()
-                        .get("name")
-                        .unwrap()
-                        .as_str()
-                        .unwrap();
-                    self.add_name(name.into());
+                    self.add_name(decl.id.name.clone());
                 }
                 _ => {}
-            },
-        );
+            }
+        }
 
         Ok(count)
     }
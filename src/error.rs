@@ -0,0 +1,224 @@
+use std::{fmt, io};
+
+use crate::ast::{Position, SourceLocation};
+
+/// Everything compiling a program can fail with, short of errors at runtime.
+/// Errors about a part of the program carry its location, and are displayed
+/// with its line like the runtime errors.
+#[derive(Debug)]
+pub enum CompileError {
+    /// The Source text doesn't parse.
+    Syntax {
+        message: String,
+        position: Position,
+    },
+    /// The input isn't the ESTree JSON of a Source §2 program.
+    InvalidEstree(serde_json::Error),
+    /// A node that's valid ESTree, but not part of Source §2, e.g. a
+    /// `WhileStatement`.
+    UnsupportedNode {
+        kind: String,
+        loc: Option<SourceLocation>,
+    },
+    /// An operator that's not part of Source §2, e.g. `typeof`.
+    UnknownOperator {
+        operator: String,
+        loc: Option<SourceLocation>,
+    },
+    UndeclaredName {
+        name: String,
+        loc: Option<SourceLocation>,
+    },
+    /// A name declared twice in the same block, or as a parameter and in the
+    /// function's body.
+    DuplicateDeclaration {
+        name: String,
+        loc: Option<SourceLocation>,
+        previous: Option<SourceLocation>,
+    },
+    AssignmentToConstant {
+        name: String,
+        loc: Option<SourceLocation>,
+        declared: Option<SourceLocation>,
+    },
+    ReturnOutsideFunction {
+        loc: Option<SourceLocation>,
+    },
+    BuiltinArity {
+        name: String,
+        expected: usize,
+        count: usize,
+        loc: Option<SourceLocation>,
+    },
+    UnknownOptLevel(u8),
+    /// The target triple, CPU or features aren't supported by LLVM.
+    Target(String),
+    /// The generated module is invalid, which is a bug in the code generator.
+    LlvmVerify(String),
+    /// An artifact couldn't be written.
+    Emit(String),
+    /// The JIT couldn't be set up to run the module.
+    Jit(String),
+}
+
+impl CompileError {
+    /// Returns the location in the program the error is about, if any.
+    pub fn loc(&self) -> Option<SourceLocation> {
+        match self {
+            CompileError::Syntax { position, .. } => Some(SourceLocation {
+                start: *position,
+                end: *position,
+            }),
+            CompileError::UnsupportedNode { loc, .. }
+            | CompileError::UnknownOperator { loc, .. }
+            | CompileError::UndeclaredName { loc, .. }
+            | CompileError::DuplicateDeclaration { loc, .. }
+            | CompileError::AssignmentToConstant { loc, .. }
+            | CompileError::ReturnOutsideFunction { loc }
+            | CompileError::BuiltinArity { loc, .. } => *loc,
+            _ => None,
+        }
+    }
+
+    /// Returns the message of the error, without its location.
+    pub fn message(&self) -> String {
+        match self {
+            CompileError::Syntax { message, .. } => message.clone(),
+            CompileError::InvalidEstree(err) => format!("Invalid ESTree JSON: {}", err),
+            CompileError::UnsupportedNode { kind, .. } => {
+                format!("{} is not supported in Source §2.", kind)
+            }
+            CompileError::UnknownOperator { operator, .. } => {
+                format!("Operator {} is not supported in Source §2.", operator)
+            }
+            CompileError::UndeclaredName { name, .. } => format!("Name {} not declared.", name),
+            CompileError::DuplicateDeclaration { name, .. } => {
+                format!("Name {} has already been declared.", name)
+            }
+            CompileError::AssignmentToConstant { name, .. } => {
+                format!("Cannot assign new value to constant {}.", name)
+            }
+            CompileError::ReturnOutsideFunction { .. } => {
+                "Cannot return outside of a function.".into()
+            }
+            CompileError::BuiltinArity {
+                expected, count, ..
+            } => format!("Expected {} arguments, but got {}.", expected, count),
+            CompileError::UnknownOptLevel(level) => {
+                format!("Unknown optimization level {}", level)
+            }
+            CompileError::Target(message) => message.clone(),
+            CompileError::LlvmVerify(message) => {
+                format!("Generated an invalid LLVM module: {}", message)
+            }
+            CompileError::Emit(message) => message.clone(),
+            CompileError::Jit(message) => message.clone(),
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(loc) = self.loc() {
+            write!(f, "Line {}: ", loc.start.line)?;
+        }
+        write!(f, "{}", self.message())
+    }
+}
+
+impl std::error::Error for CompileError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CompileError::InvalidEstree(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<serde_json::Error> for CompileError {
+    fn from(err: serde_json::Error) -> Self {
+        CompileError::InvalidEstree(err)
+    }
+}
+
+/// The errors found in a program, which are all reported at once where
+/// possible, e.g. every syntax error or every undeclared name.
+#[derive(Debug)]
+pub struct CompileErrors(pub Vec<CompileError>);
+
+impl fmt::Display for CompileErrors {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, err) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", err)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CompileErrors {}
+
+impl From<CompileError> for CompileErrors {
+    fn from(err: CompileError) -> Self {
+        CompileErrors(vec![err])
+    }
+}
+
+/// Everything interpreting a program can fail with.
+#[derive(Debug)]
+pub enum InterpretError {
+    /// The program doesn't compile, so it isn't run.
+    Compile(CompileErrors),
+    /// An error raised while running the program, displayed with the message
+    /// the compiled program prints before exiting with code 1.
+    Runtime {
+        message: String,
+        loc: Option<SourceLocation>,
+    },
+    /// What the program displays couldn't be written.
+    Io(io::Error),
+}
+
+impl fmt::Display for InterpretError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InterpretError::Compile(errors) => write!(f, "{}", errors),
+            InterpretError::Runtime {
+                message,
+                loc: Some(loc),
+            } => write!(f, "Line {}: {}", loc.start.line, message),
+            InterpretError::Runtime { message, loc: None } => write!(f, "{}", message),
+            InterpretError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for InterpretError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InterpretError::Compile(errors) => Some(errors),
+            InterpretError::Io(err) => Some(err),
+            InterpretError::Runtime { .. } => None,
+        }
+    }
+}
+
+impl From<CompileErrors> for InterpretError {
+    fn from(errors: CompileErrors) -> Self {
+        InterpretError::Compile(errors)
+    }
+}
+
+impl From<CompileError> for InterpretError {
+    fn from(err: CompileError) -> Self {
+        InterpretError::Compile(err.into())
+    }
+}
+
+impl From<io::Error> for InterpretError {
+    fn from(err: io::Error) -> Self {
+        InterpretError::Io(err)
+    }
+}
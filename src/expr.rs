@@ -1,96 +1,114 @@
 use std::rc::Rc;
 
+use crate::ast::{
+    BinaryExpression, BinaryOperator, CallExpression, ConditionalExpression, Expr, FunctionBody,
+    Identifier, Literal, LiteralValue, LogicalExpression, LogicalOperator, SourceLocation,
+    UnaryExpression, UnaryOperator,
+};
+use crate::checker::{find_builtin, is_builtin};
 use crate::env::Env;
+use crate::error::CompileError;
+use crate::gc::{GC_SLOTS, GC_VALUE};
 use crate::helper::*;
 use crate::stmt::compile_block_stmt;
-use anyhow::{anyhow, Error};
 use inkwell::{
     builder::Builder,
     context::Context,
     module::Module,
-    values::{BasicValue, BasicValueEnum, FunctionValue, IntValue, PointerValue},
-    AddressSpace, IntPredicate,
+    values::{BasicValueEnum, FloatValue, FunctionValue, IntValue},
+    AddressSpace, FloatPredicate, IntPredicate,
 };
-use serde_json::Value;
 
 pub(crate) fn compile_expr<'ctx>(
-    es_node: &Value,
+    expr: &Expr,
     env: Rc<Env<'ctx>>,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
     function: &FunctionValue<'ctx>,
-) -> Result<PointerValue<'ctx>, Error> {
-    let type_ = es_node.get("type").unwrap().as_str().unwrap();
-    // println!("{:?}", type_);
-    match type_ {
-        "Identifier" => compile_id_expr(es_node, env, context, module, builder),
-        "UnaryExpression" => compile_unary_expr(es_node, env, context, module, builder, function),
-        "BinaryExpression" | "LogicalExpression" => {
-            compile_binary_expr(es_node, env, context, module, builder, function)
+) -> Result<IntValue<'ctx>, CompileError> {
+    match expr {
+        Expr::Identifier(id) => compile_id_expr(id, env, context, module, builder),
+        Expr::UnaryExpression(expr) => {
+            compile_unary_expr(expr, env, context, module, builder, function)
+        }
+        Expr::BinaryExpression(expr) => {
+            compile_binary_expr(expr, env, context, module, builder, function)
+        }
+        Expr::LogicalExpression(expr) => {
+            compile_logical_expr(expr, env, context, module, builder, function)
+        }
+        Expr::Literal(lit) => compile_literal_expr(lit, context, module, builder),
+        Expr::CallExpression(expr) => {
+            compile_call_expr(expr, false, env, context, module, builder, function)
         }
-        "Literal" => compile_literal_expr(es_node, context, module, builder),
-        "CallExpression" => compile_call_expr(es_node, env, context, module, builder, function),
-        "ConditionalExpression" => {
-            compile_ternary_expr(es_node, env, context, module, builder, function)
+        Expr::ConditionalExpression(expr) => {
+            compile_ternary_expr(expr, false, env, context, module, builder, function)
+        }
+        Expr::ArrowFunctionExpression(expr) => compile_fn_expr(
+            None,
+            &expr.params,
+            &expr.body,
+            env,
+            context,
+            module,
+            builder,
+        ),
+        Expr::AssignmentExpression(_) => unreachable!("assignments are rejected by the checker"),
+    }
+}
+
+/// Compiles an expression in tail position, where a call is not made but
+/// returned to the caller's trampoline instead.
+pub(crate) fn compile_tail_expr<'ctx>(
+    expr: &Expr,
+    env: Rc<Env<'ctx>>,
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    function: &FunctionValue<'ctx>,
+) -> Result<IntValue<'ctx>, CompileError> {
+    match expr {
+        Expr::CallExpression(expr) => {
+            compile_call_expr(expr, true, env, context, module, builder, function)
         }
-        "ArrowFunctionExpression" => {
-            let is_expression = es_node.get("expression").unwrap().as_bool().unwrap();
-            compile_fn_expr(None, es_node, env, is_expression, context, module, builder)
+        Expr::ConditionalExpression(expr) => {
+            compile_ternary_expr(expr, true, env, context, module, builder, function)
         }
-        _ => Err(anyhow!("expr compile error")),
+        _ => compile_expr(expr, env, context, module, builder, function),
     }
 }
 
 fn compile_id_expr<'ctx>(
-    es_node: &Value,
+    id: &Identifier,
     env: Rc<Env<'ctx>>,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
-) -> Result<PointerValue<'ctx>, Error> {
-    let source_obj_type = module.get_struct_type("source_obj").unwrap();
-    let source_obj_ptr_type = source_obj_type.ptr_type(AddressSpace::Generic);
-    let source_obj_ptr_ptr_type = source_obj_ptr_type.ptr_type(AddressSpace::Generic);
-
-    let name = es_node.get("name").unwrap().as_str().unwrap();
-    let (jumps, offset) = env.lookup(name)?;
-    let mut frame = env.ptr.clone().unwrap();
-
-    (0..jumps).for_each(|_| {
-        let tmp = builder
-            .build_bitcast(*frame, frame.get_type().ptr_type(AddressSpace::Generic), "")
-            .into_pointer_value();
-        frame = Rc::new(builder.build_load(tmp, "").into_pointer_value());
-    });
+) -> Result<IntValue<'ctx>, CompileError> {
+    if id.name == "undefined" {
+        return Ok(build_undefined(context));
+    }
+    if is_builtin(&id.name) && env.lookup(&id.name).is_err() {
+        return build_builtin_closure(&id.name, context, module, builder);
+    }
 
-    let frame_casted = builder
-        .build_bitcast(*frame, source_obj_ptr_ptr_type, "")
-        .into_pointer_value();
-    // SAFETY: Inherently unsafe
-    let ptr = unsafe {
-        builder.build_in_bounds_gep(
-            frame_casted,
-            &[context.i32_type().const_int(offset, false)],
-            "",
-        )
-    };
-    let load = builder.build_load(ptr, "").into_pointer_value();
+    let ptr = build_slot_ptr(&id.name, &env, context, builder)?;
+    let load = builder.build_load(ptr, "").into_int_value();
 
     Ok(load)
 }
 
 fn compile_unary_expr<'ctx>(
-    es_node: &Value,
+    expr: &UnaryExpression,
     env: Rc<Env<'ctx>>,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
     function: &FunctionValue<'ctx>,
-) -> Result<PointerValue<'ctx>, Error> {
-    let operator = es_node.get("operator").unwrap().as_str().unwrap();
+) -> Result<IntValue<'ctx>, CompileError> {
     let argument = compile_expr(
-        es_node.get("argument").unwrap(),
+        &expr.argument,
         env.clone(),
         context,
         module,
@@ -98,17 +116,10 @@ fn compile_unary_expr<'ctx>(
         function,
     )?;
 
-    let zero = context.i32_type().const_int(0, false);
-    let one = context.i32_type().const_int(1, false);
-
-    let type_ptr = unsafe { builder.build_in_bounds_gep(argument, &[zero, zero], "") };
-    let value_ptr = unsafe { builder.build_in_bounds_gep(argument, &[zero, one], "") };
+    let obj_type = build_type_of(argument, module, builder);
 
-    let obj_type = builder.build_load(type_ptr, "").into_int_value();
-    let obj_value = builder.build_load(value_ptr, "").into_int_value();
-
-    match operator {
-        "!" => {
+    match expr.operator {
+        UnaryOperator::Not => {
             let error = context.append_basic_block(*function, "rt.tc.error");
             let valid = context.append_basic_block(*function, "rt.tc.valid");
 
@@ -121,15 +132,23 @@ fn compile_unary_expr<'ctx>(
             builder.build_conditional_branch(is_bool, valid, error);
 
             builder.position_at_end(error);
-            let error_fn = module.get_function("error").unwrap();
-            builder.build_call(error_fn, &[], "");
+            build_error(
+                UNARY_TYPE_ERROR,
+                context.i64_type().const_int(1, false),
+                obj_type,
+                expr.loc,
+                context,
+                module,
+                builder,
+            );
             builder.build_unconditional_branch(valid);
 
             builder.position_at_end(valid);
-            let not = builder.build_not(obj_value, "");
-            build_literal(&obj_type, &not, context, module, builder)
+            // true and false only differ in the lowest bit
+            let not = builder.build_xor(argument, context.i64_type().const_int(1, false), "");
+            Ok(not)
         }
-        "-" => {
+        UnaryOperator::Minus => {
             let error = context.append_basic_block(*function, "rt.tc.error");
             let valid = context.append_basic_block(*function, "rt.tc.valid");
 
@@ -142,41 +161,44 @@ fn compile_unary_expr<'ctx>(
             builder.build_conditional_branch(is_number, valid, error);
 
             builder.position_at_end(error);
-            let error_fn = module.get_function("error").unwrap();
-            builder.build_call(error_fn, &[], "");
+            build_error(
+                UNARY_TYPE_ERROR,
+                context.i64_type().const_int(2, false),
+                obj_type,
+                expr.loc,
+                context,
+                module,
+                builder,
+            );
             builder.build_unconditional_branch(valid);
 
             builder.position_at_end(valid);
-            let obj_value = builder
-                .build_bitcast(obj_value, context.f64_type(), "")
-                .into_float_value();
+            let obj_value = build_number_value(argument, context, builder);
             let neg = builder.build_float_neg(obj_value, "");
-            let neg_as_i64 = builder
-                .build_bitcast(neg, context.i64_type(), "")
-                .into_int_value();
-            build_literal(&obj_type, &neg_as_i64, context, module, builder)
+            Ok(build_number_from_f64(neg, context, builder))
         }
-        _ => Err(anyhow!("unary expr compile error")),
     }
 }
 
 fn typecheck<'ctx>(
-    expected_left_type: &IntValue,
-    expected_right_type: &IntValue,
-    actual_left_type: &IntValue,
-    actual_right_type: &IntValue,
+    expected_left_type: &IntValue<'ctx>,
+    expected_right_type: &IntValue<'ctx>,
+    actual_left_type: &IntValue<'ctx>,
+    actual_right_type: &IntValue<'ctx>,
+    loc: Option<SourceLocation>,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
     function: &FunctionValue<'ctx>,
 ) {
     let next = context.append_basic_block(*function, "rt.tc.next");
-    let error = context.append_basic_block(*function, "rt.tc.error");
+    let left_error = context.append_basic_block(*function, "rt.tc.error");
+    let right_error = context.append_basic_block(*function, "rt.tc.error");
     let valid = context.append_basic_block(*function, "rt.tc.valid");
 
     let left_match =
         builder.build_int_compare(IntPredicate::EQ, *expected_left_type, *actual_left_type, "");
-    builder.build_conditional_branch(left_match, next, error);
+    builder.build_conditional_branch(left_match, next, left_error);
 
     builder.position_at_end(next);
     let right_match = builder.build_int_compare(
@@ -185,422 +207,433 @@ fn typecheck<'ctx>(
         *actual_right_type,
         "",
     );
-    builder.build_conditional_branch(right_match, valid, error);
+    builder.build_conditional_branch(right_match, valid, right_error);
+
+    builder.position_at_end(left_error);
+    build_error(
+        LEFT_TYPE_ERROR,
+        *expected_left_type,
+        *actual_left_type,
+        loc,
+        context,
+        module,
+        builder,
+    );
+    builder.build_unconditional_branch(valid);
 
-    builder.position_at_end(error);
-    let error_fn = module.get_function("error").unwrap();
-    builder.build_call(error_fn, &[], "");
+    builder.position_at_end(right_error);
+    build_error(
+        RIGHT_TYPE_ERROR,
+        *expected_right_type,
+        *actual_right_type,
+        loc,
+        context,
+        module,
+        builder,
+    );
     builder.build_unconditional_branch(valid);
 
     builder.position_at_end(valid);
 }
 
-fn compile_binary_expr<'ctx>(
-    es_node: &Value,
-    env: Rc<Env<'ctx>>,
+/// Builds `number_op` if both operands are numbers and `string_op` if both are
+/// strings, and raises a runtime type error otherwise. Both ops return the
+/// resulting value.
+fn build_number_or_string_op<'ctx>(
+    left_type: &IntValue<'ctx>,
+    right_type: &IntValue<'ctx>,
+    number_op: impl FnOnce() -> Result<IntValue<'ctx>, CompileError>,
+    string_op: impl FnOnce() -> Result<IntValue<'ctx>, CompileError>,
+    loc: Option<SourceLocation>,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
     function: &FunctionValue<'ctx>,
-) -> Result<PointerValue<'ctx>, Error> {
-    let operator = es_node.get("operator").unwrap().as_str().unwrap();
-    let left = compile_expr(
-        es_node.get("left").unwrap(),
-        env.clone(),
-        context,
-        module,
-        builder,
-        function,
-    )?;
-    // let display_fn = module.get_function("display").unwrap();
-    // builder.build_call(display_fn, &[left.into()], "");
-    let right = compile_expr(
-        es_node.get("right").unwrap(),
-        env.clone(),
+) -> Result<IntValue<'ctx>, CompileError> {
+    let i64_type = context.i64_type();
+    let number_type = i64_type.const_int(2, false);
+    let string_type = i64_type.const_int(4, false);
+
+    let left_number_block = context.append_basic_block(*function, "rt.op.left_number");
+    let not_left_number_block = context.append_basic_block(*function, "rt.op.not_left_number");
+    let left_string_block = context.append_basic_block(*function, "rt.op.left_string");
+    let number_block = context.append_basic_block(*function, "rt.op.number");
+    let string_block = context.append_basic_block(*function, "rt.op.string");
+    let left_error = context.append_basic_block(*function, "rt.tc.error");
+    let right_number_error = context.append_basic_block(*function, "rt.tc.error");
+    let right_string_error = context.append_basic_block(*function, "rt.tc.error");
+    let end_block = context.append_basic_block(*function, "rt.op.end");
+
+    // The left operand decides what the right operand is expected to be.
+    let left_is_number = builder.build_int_compare(IntPredicate::EQ, *left_type, number_type, "");
+    builder.build_conditional_branch(left_is_number, left_number_block, not_left_number_block);
+
+    builder.position_at_end(left_number_block);
+    let right_is_number = builder.build_int_compare(IntPredicate::EQ, *right_type, number_type, "");
+    builder.build_conditional_branch(right_is_number, number_block, right_number_error);
+
+    builder.position_at_end(not_left_number_block);
+    let left_is_string = builder.build_int_compare(IntPredicate::EQ, *left_type, string_type, "");
+    builder.build_conditional_branch(left_is_string, left_string_block, left_error);
+
+    builder.position_at_end(left_string_block);
+    let right_is_string = builder.build_int_compare(IntPredicate::EQ, *right_type, string_type, "");
+    builder.build_conditional_branch(right_is_string, string_block, right_string_error);
+
+    builder.position_at_end(number_block);
+    let number_value = number_op()?;
+    let number_end = builder.get_insert_block().unwrap();
+    builder.build_unconditional_branch(end_block);
+
+    builder.position_at_end(string_block);
+    let string_value = string_op()?;
+    let string_end = builder.get_insert_block().unwrap();
+    builder.build_unconditional_branch(end_block);
+
+    for (block, kind, expected_type, actual_type) in [
+        (
+            left_error,
+            LEFT_TYPE_ERROR,
+            i64_type.const_int(STRING_OR_NUMBER, false),
+            left_type,
+        ),
+        (
+            right_number_error,
+            RIGHT_TYPE_ERROR,
+            number_type,
+            right_type,
+        ),
+        (
+            right_string_error,
+            RIGHT_TYPE_ERROR,
+            string_type,
+            right_type,
+        ),
+    ]
+    .iter()
+    {
+        builder.position_at_end(*block);
+        build_error(
+            *kind,
+            *expected_type,
+            **actual_type,
+            loc,
+            context,
+            module,
+            builder,
+        );
+        builder.build_unreachable();
+    }
+
+    builder.position_at_end(end_block);
+    let value = builder.build_phi(i64_type, "");
+    value.add_incoming(&[(&number_value, number_end), (&string_value, string_end)]);
+
+    Ok(value.as_basic_value().into_int_value())
+}
+
+/// Orders two numbers or two strings, the latter lexicographically.
+fn build_comparison<'ctx>(
+    float_predicate: FloatPredicate,
+    int_predicate: IntPredicate,
+    left_type: &IntValue<'ctx>,
+    right_type: &IntValue<'ctx>,
+    left: IntValue<'ctx>,
+    right: IntValue<'ctx>,
+    loc: Option<SourceLocation>,
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    function: &FunctionValue<'ctx>,
+) -> Result<IntValue<'ctx>, CompileError> {
+    build_number_or_string_op(
+        left_type,
+        right_type,
+        || {
+            let left_value = build_number_value(left, context, builder);
+            let right_value = build_number_value(right, context, builder);
+            let result_value_as_i1 =
+                builder.build_float_compare(float_predicate, left_value, right_value, "");
+            Ok(build_boolean_from_i1(result_value_as_i1, context, builder))
+        },
+        || {
+            let left_str = build_string_value(left, context, module, builder);
+            let right_str = build_string_value(right, context, module, builder);
+            let strcmp_fn = module.get_function("strcmp").unwrap();
+            let cmp = builder
+                .build_call(strcmp_fn, &[left_str.into(), right_str.into()], "")
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_int_value();
+            let result_value_as_i1 = builder.build_int_compare(
+                int_predicate,
+                cmp,
+                context.i32_type().const_int(0, false),
+                "",
+            );
+            Ok(build_boolean_from_i1(result_value_as_i1, context, builder))
+        },
+        loc,
         context,
         module,
         builder,
         function,
-    )?;
-    // let display_fn = module.get_function("display").unwrap();
-    // builder.build_call(display_fn, &[right.into()], "");
-
-    let zero = context.i32_type().const_int(0, false);
-    let one = context.i32_type().const_int(1, false);
-
-    let left_value_ptr = unsafe { builder.build_in_bounds_gep(left, &[zero, one], "") };
-    let left_value = builder.build_load(left_value_ptr, "");
-    let right_value_ptr = unsafe { builder.build_in_bounds_gep(right, &[zero, one], "") };
-    let right_value = builder.build_load(right_value_ptr, "");
+    )
+}
 
-    let i64_type = context.i64_type();
-    let f64_type = context.f64_type();
+fn compile_binary_expr<'ctx>(
+    expr: &BinaryExpression,
+    env: Rc<Env<'ctx>>,
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    function: &FunctionValue<'ctx>,
+) -> Result<IntValue<'ctx>, CompileError> {
+    let left = compile_expr(&expr.left, env.clone(), context, module, builder, function)?;
+    let right = compile_expr(&expr.right, env.clone(), context, module, builder, function)?;
 
-    let left_type_ptr = unsafe { builder.build_in_bounds_gep(left, &[zero, zero], "") };
-    let left_type = builder.build_load(left_type_ptr, "").into_int_value();
-    let right_type_ptr = unsafe { builder.build_in_bounds_gep(right, &[zero, zero], "") };
-    let right_type = builder.build_load(right_type_ptr, "").into_int_value();
+    let left_type = build_type_of(left, module, builder);
+    let right_type = build_type_of(right, module, builder);
 
-    let boolean_type = i64_type.const_int(1, false);
-    let number_type = i64_type.const_int(2, false);
+    let number_type = context.i64_type().const_int(2, false);
 
-    use inkwell::FloatPredicate::*;
-    let (result_value, result_type) = match operator {
-        "+" => {
-            typecheck(
-                &number_type,
-                &number_type,
-                &left_type,
-                &right_type,
-                context,
-                module,
-                builder,
-                function,
-            );
-            let left_value_as_f64 = builder
-                .build_bitcast(left_value, f64_type, "")
-                .into_float_value();
-            let right_value_as_f64 = builder
-                .build_bitcast(right_value, f64_type, "")
-                .into_float_value();
-            let result_value_as_f64 =
-                builder.build_float_add(left_value_as_f64, right_value_as_f64, "");
-            let result_value = builder
-                .build_bitcast(result_value_as_f64, i64_type, "")
-                .into_int_value();
-            (result_value, number_type)
-        }
-        "-" => {
-            typecheck(
-                &number_type,
-                &number_type,
-                &left_type,
-                &right_type,
-                context,
-                module,
-                builder,
-                function,
-            );
-            let left_value_as_f64 = builder
-                .build_bitcast(left_value, f64_type, "")
-                .into_float_value();
-            let right_value_as_f64 = builder
-                .build_bitcast(right_value, f64_type, "")
-                .into_float_value();
-            let result_value_as_f64 =
-                builder.build_float_sub(left_value_as_f64, right_value_as_f64, "");
-            let result_value = builder
-                .build_bitcast(result_value_as_f64, i64_type, "")
-                .into_int_value();
-            (result_value, number_type)
-        }
-        "*" => {
-            typecheck(
-                &number_type,
-                &number_type,
-                &left_type,
-                &right_type,
-                context,
-                module,
-                builder,
-                function,
-            );
-            let left_value_as_f64 = builder
-                .build_bitcast(left_value, f64_type, "")
-                .into_float_value();
-            let right_value_as_f64 = builder
-                .build_bitcast(right_value, f64_type, "")
-                .into_float_value();
-            let result_value_as_f64 =
-                builder.build_float_mul(left_value_as_f64, right_value_as_f64, "");
-            let result_value = builder
-                .build_bitcast(result_value_as_f64, i64_type, "")
-                .into_int_value();
-            (result_value, number_type)
-        }
-        "/" => {
+    // Arithmetic other than `+` is only defined on numbers.
+    let build_arithmetic =
+        |op: &dyn Fn(FloatValue<'ctx>, FloatValue<'ctx>) -> FloatValue<'ctx>| -> Result<IntValue<'ctx>, CompileError> {
             typecheck(
                 &number_type,
                 &number_type,
                 &left_type,
                 &right_type,
+                expr.loc,
                 context,
                 module,
                 builder,
                 function,
             );
-            let left_value_as_f64 = builder
-                .build_bitcast(left_value, f64_type, "")
-                .into_float_value();
-            let right_value_as_f64 = builder
-                .build_bitcast(right_value, f64_type, "")
-                .into_float_value();
-            let result_value_as_f64 =
-                builder.build_float_div(left_value_as_f64, right_value_as_f64, "");
-            let result_value = builder
-                .build_bitcast(result_value_as_f64, i64_type, "")
-                .into_int_value();
-            (result_value, number_type)
-        }
-        "%" => {
-            typecheck(
-                &number_type,
-                &number_type,
-                &left_type,
-                &right_type,
-                context,
-                module,
-                builder,
-                function,
-            );
-            let left_value_as_f64 = builder
-                .build_bitcast(left_value, f64_type, "")
-                .into_float_value();
-            let right_value_as_f64 = builder
-                .build_bitcast(right_value, f64_type, "")
-                .into_float_value();
-            let result_value_as_f64 =
-                builder.build_float_rem(left_value_as_f64, right_value_as_f64, "");
-            let result_value = builder
-                .build_bitcast(result_value_as_f64, i64_type, "")
+            let left_value = build_number_value(left, context, builder);
+            let right_value = build_number_value(right, context, builder);
+            let result_value = op(left_value, right_value);
+            Ok(build_number_from_f64(result_value, context, builder))
+        };
+
+    match expr.operator {
+        BinaryOperator::Plus => build_number_or_string_op(
+            &left_type,
+            &right_type,
+            || {
+                let left_value = build_number_value(left, context, builder);
+                let right_value = build_number_value(right, context, builder);
+                let result_value = builder.build_float_add(left_value, right_value, "");
+                Ok(build_number_from_f64(result_value, context, builder))
+            },
+            || {
+                let left_str = build_string_value(left, context, module, builder);
+                let right_str = build_string_value(right, context, module, builder);
+                let concat_fn = module.get_function("string_concat").unwrap();
+                let result = builder
+                    .build_call(concat_fn, &[left_str.into(), right_str.into()], "")
+                    .try_as_basic_value()
+                    .left()
+                    .unwrap()
+                    .into_pointer_value();
+                build_string_from_ptr(result, context, module, builder)
+            },
+            expr.loc,
+            context,
+            module,
+            builder,
+            function,
+        ),
+        BinaryOperator::Minus => build_arithmetic(&|l, r| builder.build_float_sub(l, r, "")),
+        BinaryOperator::Times => build_arithmetic(&|l, r| builder.build_float_mul(l, r, "")),
+        BinaryOperator::Divide => build_arithmetic(&|l, r| builder.build_float_div(l, r, "")),
+        BinaryOperator::Modulo => build_arithmetic(&|l, r| builder.build_float_rem(l, r, "")),
+        BinaryOperator::Less => build_comparison(
+            FloatPredicate::OLT,
+            IntPredicate::SLT,
+            &left_type,
+            &right_type,
+            left,
+            right,
+            expr.loc,
+            context,
+            module,
+            builder,
+            function,
+        ),
+        BinaryOperator::Greater => build_comparison(
+            FloatPredicate::OGT,
+            IntPredicate::SGT,
+            &left_type,
+            &right_type,
+            left,
+            right,
+            expr.loc,
+            context,
+            module,
+            builder,
+            function,
+        ),
+        BinaryOperator::StrictEqual | BinaryOperator::StrictNotEqual => {
+            let strict_equal_fn = module.get_function("strict_equal").unwrap();
+            let equal = builder
+                .build_call(strict_equal_fn, &[left.into(), right.into()], "")
+                .try_as_basic_value()
+                .left()
+                .unwrap()
                 .into_int_value();
-            (result_value, number_type)
-        }
-        "<" => {
-            typecheck(
-                &number_type,
-                &number_type,
-                &left_type,
-                &right_type,
-                context,
-                module,
-                builder,
-                function,
-            );
-            let left_value_as_f64 = builder
-                .build_bitcast(left_value, f64_type, "")
-                .into_float_value();
-            let right_value_as_f64 = builder
-                .build_bitcast(right_value, f64_type, "")
-                .into_float_value();
-            let result_value_as_i1 =
-                builder.build_float_compare(OLT, left_value_as_f64, right_value_as_f64, "");
-            let result_value = builder.build_int_cast(result_value_as_i1, i64_type, "");
-            (result_value, boolean_type)
-        }
-        ">" => {
-            typecheck(
-                &number_type,
-                &number_type,
-                &left_type,
-                &right_type,
-                context,
-                module,
-                builder,
-                function,
-            );
-            let left_value_as_f64 = builder
-                .build_bitcast(left_value, f64_type, "")
-                .into_float_value();
-            let right_value_as_f64 = builder
-                .build_bitcast(right_value, f64_type, "")
-                .into_float_value();
-            let result_value_as_i1 =
-                builder.build_float_compare(OGT, left_value_as_f64, right_value_as_f64, "");
-            let result_value = builder.build_int_cast(result_value_as_i1, i64_type, "");
-            (result_value, boolean_type)
-        }
-        "===" => {
-            typecheck(
-                &number_type,
-                &number_type,
-                &left_type,
-                &right_type,
-                context,
-                module,
-                builder,
-                function,
-            );
-            let left_value_as_f64 = builder
-                .build_bitcast(left_value, f64_type, "")
-                .into_float_value();
-            let right_value_as_f64 = builder
-                .build_bitcast(right_value, f64_type, "")
-                .into_float_value();
-            let result_value_as_i1 =
-                builder.build_float_compare(OEQ, left_value_as_f64, right_value_as_f64, "");
-            let result_value = builder.build_int_cast(result_value_as_i1, i64_type, "");
-            (result_value, boolean_type)
-        }
-        "!==" => {
-            typecheck(
-                &number_type,
-                &number_type,
-                &left_type,
-                &right_type,
-                context,
-                module,
-                builder,
-                function,
-            );
-            let left_value_as_f64 = builder
-                .build_bitcast(left_value, f64_type, "")
-                .into_float_value();
-            let right_value_as_f64 = builder
-                .build_bitcast(right_value, f64_type, "")
-                .into_float_value();
-            let result_value_as_i1 =
-                builder.build_float_compare(ONE, left_value_as_f64, right_value_as_f64, "");
-            let result_value = builder.build_int_cast(result_value_as_i1, i64_type, "");
-            (result_value, boolean_type)
-        }
-        "<=" => {
-            typecheck(
-                &number_type,
-                &number_type,
-                &left_type,
-                &right_type,
-                context,
-                module,
-                builder,
-                function,
-            );
-            let left_value_as_f64 = builder
-                .build_bitcast(left_value, f64_type, "")
-                .into_float_value();
-            let right_value_as_f64 = builder
-                .build_bitcast(right_value, f64_type, "")
-                .into_float_value();
-            let result_value_as_i1 =
-                builder.build_float_compare(OLE, left_value_as_f64, right_value_as_f64, "");
-            let result_value = builder.build_int_cast(result_value_as_i1, i64_type, "");
-            (result_value, boolean_type)
-        }
-        ">=" => {
-            typecheck(
-                &number_type,
-                &number_type,
-                &left_type,
-                &right_type,
-                context,
-                module,
-                builder,
-                function,
-            );
-            let left_value_as_f64 = builder
-                .build_bitcast(left_value, f64_type, "")
-                .into_float_value();
-            let right_value_as_f64 = builder
-                .build_bitcast(right_value, f64_type, "")
-                .into_float_value();
-            let result_value_as_i1 =
-                builder.build_float_compare(OGE, left_value_as_f64, right_value_as_f64, "");
-            let result_value = builder.build_int_cast(result_value_as_i1, i64_type, "");
-            (result_value, boolean_type)
-        }
-        "&&" => {
-            typecheck(
-                &boolean_type,
-                &boolean_type,
-                &left_type,
-                &right_type,
-                context,
-                module,
-                builder,
-                function,
-            );
-            let result_value = builder.build_and(
-                left_value.into_int_value(),
-                right_value.into_int_value(),
-                "",
-            );
-            (result_value, boolean_type)
-        }
-        "||" => {
-            typecheck(
-                &boolean_type,
-                &boolean_type,
-                &left_type,
-                &right_type,
-                context,
-                module,
-                builder,
-                function,
-            );
-            let result_value = builder.build_or(
-                left_value.into_int_value(),
-                right_value.into_int_value(),
-                "",
-            );
-            (result_value, boolean_type)
+            let result_value_as_i1 = if expr.operator == BinaryOperator::StrictEqual {
+                equal
+            } else {
+                builder.build_not(equal, "")
+            };
+            Ok(build_boolean_from_i1(result_value_as_i1, context, builder))
         }
-        _ => return Err(anyhow!("binary expr compile error")),
+        BinaryOperator::LessEqual => build_comparison(
+            FloatPredicate::OLE,
+            IntPredicate::SLE,
+            &left_type,
+            &right_type,
+            left,
+            right,
+            expr.loc,
+            context,
+            module,
+            builder,
+            function,
+        ),
+        BinaryOperator::GreaterEqual => build_comparison(
+            FloatPredicate::OGE,
+            IntPredicate::SGE,
+            &left_type,
+            &right_type,
+            left,
+            right,
+            expr.loc,
+            context,
+            module,
+            builder,
+            function,
+        ),
+    }
+}
+
+/// `&&` and `||` only evaluate their right operand if needed. The left operand
+/// has to be a boolean, but the right operand's value is returned as is.
+fn compile_logical_expr<'ctx>(
+    expr: &LogicalExpression,
+    env: Rc<Env<'ctx>>,
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    function: &FunctionValue<'ctx>,
+) -> Result<IntValue<'ctx>, CompileError> {
+    let left = compile_expr(&expr.left, env.clone(), context, module, builder, function)?;
+
+    let left_type = build_type_of(left, module, builder);
+
+    // typecheck
+    {
+        let error = context.append_basic_block(*function, "rt.tc.error");
+        let valid = context.append_basic_block(*function, "rt.tc.valid");
+
+        let is_bool = builder.build_int_compare(
+            IntPredicate::EQ,
+            left_type,
+            context.i64_type().const_int(1, false),
+            "",
+        );
+        builder.build_conditional_branch(is_bool, valid, error);
+
+        builder.position_at_end(error);
+        build_error(
+            LEFT_TYPE_ERROR,
+            context.i64_type().const_int(1, false),
+            left_type,
+            expr.loc,
+            context,
+            module,
+            builder,
+        );
+        builder.build_unconditional_branch(valid);
+
+        builder.position_at_end(valid);
+    }
+
+    let as_i1 = build_boolean_value(left, context, builder);
+
+    let right_block = context.append_basic_block(*function, "logical.right");
+    let end_block = context.append_basic_block(*function, "logical.end");
+
+    match expr.operator {
+        LogicalOperator::And => builder.build_conditional_branch(as_i1, right_block, end_block),
+        LogicalOperator::Or => builder.build_conditional_branch(as_i1, end_block, right_block),
     };
+    let left_end = builder.get_insert_block().unwrap();
+
+    builder.position_at_end(right_block);
+    let right = compile_expr(&expr.right, env, context, module, builder, function)?;
+    let right_end = builder.get_insert_block().unwrap();
+    builder.build_unconditional_branch(end_block);
 
-    // println!("{:?}", result_type);
-    // println!("{:?}", result_value);
-    build_literal(&result_type, &result_value, context, module, builder)
+    builder.position_at_end(end_block);
+    let phi = builder.build_phi(context.i64_type(), "");
+    phi.add_incoming(&[(&left, left_end), (&right, right_end)]);
+
+    Ok(phi.as_basic_value().into_int_value())
 }
 
 fn compile_literal_expr<'ctx>(
-    es_node: &Value,
+    lit: &Literal,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
-) -> Result<PointerValue<'ctx>, Error> {
-    match es_node.get("value").unwrap() {
-        Value::Bool(value) => build_boolean(*value, context, module, builder),
-        Value::Number(value) => build_number(value.as_f64().unwrap(), context, module, builder),
-        _ => return Err(anyhow!("literal expr compile error")),
+) -> Result<IntValue<'ctx>, CompileError> {
+    match &lit.value {
+        LiteralValue::Null => Ok(build_null(context)),
+        LiteralValue::Boolean(value) => Ok(build_boolean(*value, context)),
+        LiteralValue::Number(value) => Ok(build_number(*value, context)),
+        LiteralValue::String(value) => build_string(value, context, module, builder),
     }
 }
 
 fn compile_call_expr<'ctx>(
-    es_node: &Value,
+    expr: &CallExpression,
+    tail: bool,
     env: Rc<Env<'ctx>>,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
     function: &FunctionValue<'ctx>,
-) -> Result<PointerValue<'ctx>, Error> {
-    let params: Vec<BasicValueEnum<'ctx>> = es_node
-        .get("arguments")
-        .unwrap()
-        .as_array()
-        .unwrap()
+) -> Result<IntValue<'ctx>, CompileError> {
+    let params = expr
+        .arguments
         .iter()
         .map(|arg| {
             compile_expr(arg, env.clone(), context, module, builder, function)
-                .unwrap()
-                .as_basic_value_enum()
+                .map(|value| value.into())
         })
-        .collect();
-
-    if es_node
-        .get("callee")
-        .unwrap()
-        .get("type")
-        .unwrap()
-        .as_str()
-        .unwrap()
-        == "Identifier"
-    {
-        let callee_name = es_node
-            .get("callee")
-            .unwrap()
-            .get("name")
-            .unwrap()
-            .as_str()
-            .unwrap();
-        if callee_name == "display" {
-            let display_fn = module.get_function("display").unwrap();
-            builder.build_call(display_fn, &params, "");
-            return build_undefined(context, module, builder);
+        .collect::<Result<Vec<BasicValueEnum<'ctx>>, CompileError>>()?;
+
+    if let Expr::Identifier(callee) = &*expr.callee {
+        if is_builtin(&callee.name) && env.lookup(&callee.name).is_err() {
+            let args: Vec<_> = params.iter().map(|arg| arg.into_int_value()).collect();
+            return compile_builtin_call(
+                &callee.name,
+                &args,
+                expr.loc,
+                context,
+                module,
+                builder,
+                function,
+            );
         }
     }
 
     let callee = compile_expr(
-        es_node.get("callee").unwrap(),
+        &expr.callee,
         env.clone(),
         context,
         module,
@@ -608,9 +641,7 @@ fn compile_call_expr<'ctx>(
         function,
     )?;
 
-    let source_obj_type = module.get_struct_type("source_obj").unwrap();
-    let source_obj_ptr_type = source_obj_type.ptr_type(AddressSpace::Generic);
-    let source_obj_ptr_ptr_type = source_obj_ptr_type.ptr_type(AddressSpace::Generic);
+    let frame_type = context.i64_type().ptr_type(AddressSpace::Generic);
 
     let closure_type = module.get_struct_type("closure").unwrap();
     let closure_ptr_type = closure_type.ptr_type(AddressSpace::Generic);
@@ -618,9 +649,9 @@ fn compile_call_expr<'ctx>(
     let _0 = context.i32_type().const_int(0, false);
     let _1 = context.i32_type().const_int(1, false);
     let _2 = context.i32_type().const_int(2, false);
+    let _3 = context.i32_type().const_int(3, false);
 
-    let lit_type = unsafe { builder.build_in_bounds_gep(callee, &[_0, _0], "") };
-    let lit_type_value = builder.build_load(lit_type, "").into_int_value();
+    let lit_type_value = build_type_of(callee, module, builder);
 
     // typecheck
     {
@@ -636,16 +667,51 @@ fn compile_call_expr<'ctx>(
         builder.build_conditional_branch(is_fn, next, error);
 
         builder.position_at_end(error);
-        let error_fn = module.get_function("error").unwrap();
-        builder.build_call(error_fn, &[], "");
+        build_error(
+            CALL_NON_FUNCTION_ERROR,
+            context.i64_type().const_int(0, false),
+            callee,
+            expr.loc,
+            context,
+            module,
+            builder,
+        );
         builder.build_unconditional_branch(next);
 
         builder.position_at_end(next);
     }
 
-    let function_lit = builder
-        .build_bitcast(callee, closure_ptr_type, "")
-        .into_pointer_value();
+    let function_lit = build_unbox(callee, closure_ptr_type, context, builder);
+
+    // arity check
+    {
+        let error = context.append_basic_block(*function, "rt.arity.error");
+        let valid = context.append_basic_block(*function, "rt.arity.valid");
+
+        let arity_addr = unsafe { builder.build_in_bounds_gep(function_lit, &[_0, _3], "") };
+        let arity = builder.build_load(arity_addr, "").into_int_value();
+        let arg_count = context.i64_type().const_int(params.len() as u64, false);
+
+        let arity_match = builder.build_int_compare(IntPredicate::EQ, arity, arg_count, "");
+        let any_arity = context.i64_type().const_int(ANY_ARITY, false);
+        let takes_any = builder.build_int_compare(IntPredicate::EQ, arity, any_arity, "");
+        let arity_match = builder.build_or(arity_match, takes_any, "");
+        builder.build_conditional_branch(arity_match, valid, error);
+
+        builder.position_at_end(error);
+        build_error(
+            ARITY_ERROR,
+            arity,
+            arg_count,
+            expr.loc,
+            context,
+            module,
+            builder,
+        );
+        builder.build_unconditional_branch(valid);
+
+        builder.position_at_end(valid);
+    }
 
     let function_obj_addr = unsafe { builder.build_in_bounds_gep(function_lit, &[_0, _2], "") };
     let function_obj = builder
@@ -657,11 +723,11 @@ fn compile_call_expr<'ctx>(
 
     let boxed_params = {
         let n = params.len();
-        let size = n * 8;
+        let size = n as u64 * size_of(&context.i64_type(), module);
 
-        let mem = malloc(size as u64, context, module, builder, "params")?;
+        let mem = gc_alloc(GC_SLOTS, size, context, module, builder, "params")?;
         let addr = builder
-            .build_bitcast(mem, source_obj_ptr_ptr_type, "")
+            .build_bitcast(mem, frame_type, "")
             .into_pointer_value();
 
         let mut base;
@@ -676,30 +742,398 @@ fn compile_call_expr<'ctx>(
             builder.build_store(base, params[i]);
         }
 
-        builder.build_bitcast(addr, source_obj_ptr_ptr_type, "")
+        addr
     };
 
-    Ok(builder
-        .build_call(function_obj, &[fun_env, boxed_params], "")
+    if tail {
+        let tail_call_type = module.get_struct_type("tail_call").unwrap();
+        let size = size_of(&tail_call_type, module);
+        let mem = gc_alloc(GC_VALUE, size, context, module, builder, "tail_call")?;
+        let tail_call = builder
+            .build_bitcast(mem, tail_call_type.ptr_type(AddressSpace::Generic), "")
+            .into_pointer_value();
+
+        let type_ptr = unsafe { builder.build_in_bounds_gep(tail_call, &[_0, _0], "") };
+        let callee_ptr = unsafe { builder.build_in_bounds_gep(tail_call, &[_0, _1], "") };
+        let params_ptr = unsafe { builder.build_in_bounds_gep(tail_call, &[_0, _2], "") };
+        builder.build_store(type_ptr, context.i64_type().const_int(5, false));
+        builder.build_store(callee_ptr, function_lit);
+        builder.build_store(params_ptr, boxed_params);
+
+        return Ok(build_box(tail_call, context, builder));
+    }
+
+    // the call takes a frame on the native stack until it returns, so the
+    // depth of calls is limited before the stack overflows
+    let call_depth = module.get_global("call_depth").unwrap().as_pointer_value();
+    let depth = builder.build_load(call_depth, "").into_int_value();
+    {
+        let error = context.append_basic_block(*function, "rt.depth.error");
+        let valid = context.append_basic_block(*function, "rt.depth.valid");
+
+        let max_depth = context.i32_type().const_int(MAX_CALL_DEPTH, false);
+        let too_deep = builder.build_int_compare(IntPredicate::UGE, depth, max_depth, "");
+        builder.build_conditional_branch(too_deep, error, valid);
+
+        builder.position_at_end(error);
+        let undefined = build_undefined(context);
+        build_error(
+            STACK_OVERFLOW_ERROR,
+            undefined,
+            undefined,
+            None,
+            context,
+            module,
+            builder,
+        );
+        builder.build_unconditional_branch(valid);
+
+        builder.position_at_end(valid);
+    }
+    builder.build_store(call_depth, builder.build_int_add(depth, _1, ""));
+
+    // everything the callee allocates but its result is garbage after the call
+    let roots_top = module
+        .get_global("gc_roots_top")
+        .unwrap()
+        .as_pointer_value();
+    let saved_roots_top = builder.build_load(roots_top, "");
+
+    let result = builder
+        .build_call(function_obj, &[fun_env, boxed_params.into()], "")
+        .try_as_basic_value()
+        .left()
+        .unwrap();
+    let trampoline_fn = module.get_function("trampoline").unwrap();
+    let result = builder
+        .build_call(trampoline_fn, &[result], "")
         .try_as_basic_value()
         .left()
         .unwrap()
-        .into_pointer_value())
+        .into_int_value();
+
+    builder.build_store(call_depth, depth);
+    builder.build_store(roots_top, saved_roots_top);
+    let push_root_fn = module.get_function("gc_push_root").unwrap();
+    builder.build_call(push_root_fn, &[result.into()], "");
+
+    Ok(result)
 }
 
-fn compile_ternary_expr<'ctx>(
-    es_node: &Value,
+/// Compiles a call to a function built into the code generator, which the
+/// checker has made sure gets the number of arguments it takes.
+fn compile_builtin_call<'ctx>(
+    name: &str,
+    args: &[IntValue<'ctx>],
+    loc: Option<SourceLocation>,
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    function: &FunctionValue<'ctx>,
+) -> Result<IntValue<'ctx>, CompileError> {
+    match name {
+        "display" => {
+            let display_fn = module.get_function("display").unwrap();
+            builder.build_call(display_fn, &[args[0].into()], "");
+            Ok(build_undefined(context))
+        }
+        "pair" => build_pair(args[0], args[1], context, module, builder),
+        // a list is built from its last element
+        "list" => args
+            .iter()
+            .rev()
+            .try_fold(build_null(context), |tail, head| {
+                build_pair(*head, tail, context, module, builder)
+            }),
+        "head" | "tail" => {
+            build_pair_check(args[0], loc, context, module, builder, function);
+            let field_ptr = build_pair_field_ptr(args[0], name == "tail", context, module, builder);
+            let value = builder.build_load(field_ptr, "").into_int_value();
+            // A `set_head` or `set_tail` may take the value out of the pair
+            // before it is used, e.g. in `f(head(p), g())`, so it is rooted
+            // like a new allocation until the enclosing call returns.
+            let push_root_fn = module.get_function("gc_push_root").unwrap();
+            builder.build_call(push_root_fn, &[value.into()], "");
+            Ok(value)
+        }
+        "set_head" | "set_tail" => {
+            build_pair_check(args[0], loc, context, module, builder, function);
+            let field_ptr =
+                build_pair_field_ptr(args[0], name == "set_tail", context, module, builder);
+            builder.build_store(field_ptr, args[1]);
+            Ok(build_undefined(context))
+        }
+        "is_pair" => {
+            let type_ = build_type_of(args[0], module, builder);
+            let is_pair = builder.build_int_compare(
+                IntPredicate::EQ,
+                type_,
+                context.i64_type().const_int(6, false),
+                "",
+            );
+            Ok(build_boolean_from_i1(is_pair, context, builder))
+        }
+        "is_null" => {
+            let is_null = builder.build_int_compare(
+                IntPredicate::EQ,
+                args[0],
+                context.i64_type().const_int(NULL, false),
+                "",
+            );
+            Ok(build_boolean_from_i1(is_null, context, builder))
+        }
+        "stringify" => {
+            let stringify_fn = module.get_function("stringify").unwrap();
+            let string_ptr = builder
+                .build_call(stringify_fn, &[args[0].into()], "")
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_pointer_value();
+            build_string_from_ptr(string_ptr, context, module, builder)
+        }
+        _ => unreachable!("{} is not a built-in function", name),
+    }
+}
+
+/// Returns the closure of a built-in function used as a value, e.g. in
+/// `map(head, xs)`, whose code makes the same inlined call as a direct call
+/// does. The closure isn't allocated but a global with a marked header of its
+/// own, so that collections neither trace nor free it and every use of the
+/// built-in function is the same value.
+fn build_builtin_closure<'ctx>(
+    name: &str,
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+) -> Result<IntValue<'ctx>, CompileError> {
+    let closure_name = format!("builtin.{}.closure", name);
+    let global = match module.get_global(&closure_name) {
+        Some(global) => global,
+        None => {
+            let i32_type = context.i32_type();
+            let i64_type = context.i64_type();
+            let size_type = size_type(context, module);
+            let frame_type = i64_type.ptr_type(AddressSpace::Generic);
+            let header_type = module.get_struct_type("gc_header").unwrap();
+            let closure_type = module.get_struct_type("closure").unwrap();
+
+            let (_, arity) = find_builtin(name).unwrap();
+            let arity = arity.map_or(ANY_ARITY, |arity| arity as u64);
+            let fun = compile_builtin_fn(name, arity, context, module, builder)?;
+
+            let header = header_type.const_named_struct(&[
+                header_type
+                    .ptr_type(AddressSpace::Generic)
+                    .const_null()
+                    .into(),
+                i32_type.const_int(GC_VALUE, false).into(),
+                i32_type.const_int(1, false).into(),
+                size_type
+                    .const_int(size_of(&closure_type, module), false)
+                    .into(),
+            ]);
+            let closure = closure_type.const_named_struct(&[
+                i64_type.const_int(3, false).into(),
+                frame_type.const_null().into(),
+                fun.as_global_value().as_pointer_value().into(),
+                i64_type.const_int(arity, false).into(),
+            ]);
+            let object = context.const_struct(&[header.into(), closure.into()], false);
+
+            let global = module.add_global(object.get_type(), None, &closure_name);
+            global.set_initializer(&object);
+            global
+        }
+    };
+
+    let _0 = context.i32_type().const_int(0, false);
+    let _1 = context.i32_type().const_int(1, false);
+    let closure = unsafe { global.as_pointer_value().const_in_bounds_gep(&[_0, _1]) };
+    Ok(build_box(closure, context, builder))
+}
+
+/// Compiles the code of the closure of a built-in function, which takes its
+/// arguments from the params like a compiled function but has no frame.
+fn compile_builtin_fn<'ctx>(
+    name: &str,
+    arity: u64,
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+) -> Result<FunctionValue<'ctx>, CompileError> {
+    let i32_type = context.i32_type();
+    let i64_type = context.i64_type();
+    let frame_type = i64_type.ptr_type(AddressSpace::Generic);
+
+    let resume_point = builder.get_insert_block().unwrap();
+
+    let generic_fn_type = i64_type.fn_type(&[frame_type.into(), frame_type.into()], false);
+    let fun = module.add_function(&format!("builtin.{}", name), generic_fn_type, None);
+
+    let entry = context.append_basic_block(fun, "f.entry");
+    builder.position_at_end(entry);
+    let params_ptr = fun.get_last_param().unwrap().into_pointer_value();
+
+    let result = if arity == ANY_ARITY {
+        // `list` is the only built-in function taking any number of
+        // arguments, which it counts from the size of the params
+        let size_type = size_type(context, module);
+        let header_type = module.get_struct_type("gc_header").unwrap();
+        let loop_block = context.append_basic_block(fun, "loop");
+        let body_block = context.append_basic_block(fun, "body");
+        let end_block = context.append_basic_block(fun, "end");
+
+        let header = builder
+            .build_bitcast(params_ptr, header_type.ptr_type(AddressSpace::Generic), "")
+            .into_pointer_value();
+        let header = unsafe {
+            builder.build_in_bounds_gep(header, &[i32_type.const_int(-1i64 as u64, true)], "")
+        };
+        let size_ptr = unsafe {
+            builder.build_in_bounds_gep(
+                header,
+                &[i32_type.const_zero(), i32_type.const_int(3, false)],
+                "",
+            )
+        };
+        let size = builder.build_load(size_ptr, "").into_int_value();
+        let slot_size = size_type.const_int(size_of(&i64_type, module), false);
+        let count = builder.build_int_unsigned_div(size, slot_size, "");
+        builder.build_unconditional_branch(loop_block);
+
+        // a list is built from its last element
+        builder.position_at_end(loop_block);
+        let i = builder.build_phi(size_type, "i");
+        let list = builder.build_phi(i64_type, "list");
+        let i_value = i.as_basic_value().into_int_value();
+        let list_value = list.as_basic_value().into_int_value();
+        let is_done =
+            builder.build_int_compare(IntPredicate::EQ, i_value, size_type.const_zero(), "");
+        builder.build_conditional_branch(is_done, end_block, body_block);
+
+        builder.position_at_end(body_block);
+        let next_i = builder.build_int_sub(i_value, size_type.const_int(1, false), "");
+        let head_ptr = unsafe { builder.build_in_bounds_gep(params_ptr, &[next_i], "") };
+        let head = builder.build_load(head_ptr, "").into_int_value();
+        let next_list = build_pair(head, list_value, context, module, builder)?;
+        let body_end = builder.get_insert_block().unwrap();
+        builder.build_unconditional_branch(loop_block);
+
+        i.add_incoming(&[(&count, entry), (&next_i, body_end)]);
+        list.add_incoming(&[(&build_null(context), entry), (&next_list, body_end)]);
+
+        builder.position_at_end(end_block);
+        list_value
+    } else {
+        let args = (0..arity)
+            .map(|i| {
+                let arg_ptr = unsafe {
+                    builder.build_in_bounds_gep(params_ptr, &[i32_type.const_int(i, false)], "")
+                };
+                builder.build_load(arg_ptr, "").into_int_value()
+            })
+            .collect::<Vec<_>>();
+        // errors are reported without a location, as the call could be anywhere
+        compile_builtin_call(name, &args, None, context, module, builder, &fun)?
+    };
+    builder.build_return(Some(&result));
+
+    builder.position_at_end(resume_point);
+
+    Ok(fun)
+}
+
+/// Raises a runtime type error unless the value is a pair.
+fn build_pair_check<'ctx>(
+    value: IntValue<'ctx>,
+    loc: Option<SourceLocation>,
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    function: &FunctionValue<'ctx>,
+) {
+    let error = context.append_basic_block(*function, "rt.tc.error");
+    let valid = context.append_basic_block(*function, "rt.tc.valid");
+
+    let type_ = build_type_of(value, module, builder);
+    let pair_type = context.i64_type().const_int(6, false);
+    let is_pair = builder.build_int_compare(IntPredicate::EQ, type_, pair_type, "");
+    builder.build_conditional_branch(is_pair, valid, error);
+
+    builder.position_at_end(error);
+    build_error(
+        UNARY_TYPE_ERROR,
+        pair_type,
+        type_,
+        loc,
+        context,
+        module,
+        builder,
+    );
+    builder.build_unconditional_branch(valid);
+
+    builder.position_at_end(valid);
+}
+
+/// Compiles the test of an `if` statement or conditional expression, which
+/// has to evaluate to a boolean.
+pub(crate) fn compile_condition<'ctx>(
+    test: &Expr,
+    loc: Option<SourceLocation>,
     env: Rc<Env<'ctx>>,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
     function: &FunctionValue<'ctx>,
-) -> Result<PointerValue<'ctx>, Error> {
-    let source_obj_type = module.get_struct_type("source_obj").unwrap();
-    let source_obj_ptr_type = source_obj_type.ptr_type(AddressSpace::Generic);
+) -> Result<IntValue<'ctx>, CompileError> {
+    let test_value = compile_expr(test, env, context, module, builder, function)?;
+
+    let test_type = build_type_of(test_value, module, builder);
+
+    // typecheck
+    {
+        let error = context.append_basic_block(*function, "rt.tc.error");
+        let valid = context.append_basic_block(*function, "rt.tc.valid");
+
+        let is_bool = builder.build_int_compare(
+            IntPredicate::EQ,
+            test_type,
+            context.i64_type().const_int(1, false),
+            "",
+        );
+        builder.build_conditional_branch(is_bool, valid, error);
 
-    let test_ptr = compile_expr(
-        es_node.get("test").unwrap(),
+        builder.position_at_end(error);
+        build_error(
+            CONDITION_TYPE_ERROR,
+            context.i64_type().const_int(1, false),
+            test_type,
+            loc,
+            context,
+            module,
+            builder,
+        );
+        builder.build_unconditional_branch(valid);
+
+        builder.position_at_end(valid);
+    }
+
+    Ok(build_boolean_value(test_value, context, builder))
+}
+
+fn compile_ternary_expr<'ctx>(
+    expr: &ConditionalExpression,
+    tail: bool,
+    env: Rc<Env<'ctx>>,
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+    function: &FunctionValue<'ctx>,
+) -> Result<IntValue<'ctx>, CompileError> {
+    let as_i1 = compile_condition(
+        &expr.test,
+        expr.loc,
         env.clone(),
         context,
         module,
@@ -707,24 +1141,21 @@ fn compile_ternary_expr<'ctx>(
         function,
     )?;
 
-    let _0 = context.i32_type().const_int(0, false);
-    let _1 = context.i32_type().const_int(1, false);
-
-    let test_result_value_ptr = unsafe { builder.build_in_bounds_gep(test_ptr, &[_0, _1], "") };
-    let value = builder
-        .build_load(test_result_value_ptr, "")
-        .into_int_value();
-    let as_i1 = builder.build_int_truncate(value, context.bool_type(), "");
-
     let consequent_block = context.append_basic_block(*function, "tern.true");
     let alternate_block = context.append_basic_block(*function, "tern.false");
     let end_block = context.append_basic_block(*function, "tern.end");
 
     builder.build_conditional_branch(as_i1, consequent_block, alternate_block);
 
+    let compile_branch = if tail {
+        compile_tail_expr
+    } else {
+        compile_expr
+    };
+
     builder.position_at_end(consequent_block);
-    let consequent = compile_expr(
-        es_node.get("consequent").unwrap(),
+    let consequent = compile_branch(
+        &expr.consequent,
         env.clone(),
         context,
         module,
@@ -735,8 +1166,8 @@ fn compile_ternary_expr<'ctx>(
     builder.build_unconditional_branch(end_block);
 
     builder.position_at_end(alternate_block);
-    let alternate = compile_expr(
-        es_node.get("alternate").unwrap(),
+    let alternate = compile_branch(
+        &expr.alternate,
         env.clone(),
         context,
         module,
@@ -747,37 +1178,27 @@ fn compile_ternary_expr<'ctx>(
     builder.build_unconditional_branch(end_block);
 
     builder.position_at_end(end_block);
-    let phi = builder.build_phi(source_obj_ptr_type, "");
+    let phi = builder.build_phi(context.i64_type(), "");
     phi.add_incoming(&[(&consequent, con_end), (&alternate, alt_end)]);
 
-    Ok(phi.as_basic_value().into_pointer_value())
+    Ok(phi.as_basic_value().into_int_value())
 }
 
 pub(crate) fn compile_fn_expr<'ctx>(
     name: Option<&str>,
-    es_node: &Value,
+    params: &[Identifier],
+    body: &FunctionBody,
     parent: Rc<Env<'ctx>>,
-    is_expression: bool,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
-) -> Result<PointerValue<'ctx>, Error> {
-    let source_obj_type = module.get_struct_type("source_obj").unwrap();
-    let source_obj_ptr_type = source_obj_type.ptr_type(AddressSpace::Generic);
-    let source_obj_ptr_ptr_type = source_obj_ptr_type.ptr_type(AddressSpace::Generic);
-    let source_obj_ptr_ptr_ptr_type = source_obj_ptr_ptr_type.ptr_type(AddressSpace::Generic);
+) -> Result<IntValue<'ctx>, CompileError> {
+    let i64_type = context.i64_type();
+    let frame_type = i64_type.ptr_type(AddressSpace::Generic);
 
     let resume_point = builder.get_insert_block().unwrap();
 
-    let params = es_node.get("params").unwrap().as_array().unwrap();
-
-    let generic_fn_type = source_obj_ptr_type.fn_type(
-        &[
-            source_obj_ptr_ptr_type.into(),
-            source_obj_ptr_ptr_type.into(),
-        ],
-        false,
-    );
+    let generic_fn_type = i64_type.fn_type(&[frame_type.into(), frame_type.into()], false);
 
     let fun = module.add_function(
         &name
@@ -797,37 +1218,22 @@ pub(crate) fn compile_fn_expr<'ctx>(
 
     params
         .iter()
-        .for_each(|param| env.add_name(param.get("name").unwrap().as_str().unwrap().into()));
+        .for_each(|param| env.add_name(param.name.clone()));
 
-    let body: &[Value] = if is_expression {
-        &[]
-    } else {
-        es_node
-            .get("body")
-            .unwrap()
-            .get("body")
-            .unwrap()
-            .as_array()
-            .unwrap()
+    let decls = match body {
+        FunctionBody::Block(block) => &block.body[..],
+        FunctionBody::Expression(_) => &[],
     };
-    let env_size = (env.add_and_count_decls(body)? + params.len() as u64 + 1) * 8;
-    let addr = malloc(env_size, context, module, builder, "fn.env")?;
+    let slot_count = env.add_and_count_decls(decls)? + params.len() as u64 + 1;
+    let env_size = slot_count * size_of(&i64_type, module);
+    let addr = gc_alloc(GC_SLOTS, env_size, context, module, builder, "fn.env")?;
     let env_value = builder
-        .build_bitcast(addr, source_obj_ptr_ptr_type, "")
+        .build_bitcast(addr, frame_type, "")
         .into_pointer_value();
     env.ptr = Some(Rc::new(env_value));
 
-    let frame_ptr = builder
-        .build_bitcast(env_value, source_obj_ptr_ptr_ptr_type, "frame")
-        .into_pointer_value();
-    builder.build_store(frame_ptr, enclosing_frame);
-
-    let params_ = builder
-        .build_bitcast(params_ptr, source_obj_ptr_ptr_type, "")
-        .into_pointer_value();
-    let this_env = builder
-        .build_bitcast(*env.ptr.clone().unwrap(), source_obj_ptr_ptr_type, "")
-        .into_pointer_value();
+    let enclosing_frame_value = build_box(enclosing_frame, context, builder);
+    builder.build_store(env_value, enclosing_frame_value);
 
     let mut base;
     let mut value;
@@ -835,7 +1241,7 @@ pub(crate) fn compile_fn_expr<'ctx>(
     for i in 0..params.len() {
         base = unsafe {
             builder.build_in_bounds_gep(
-                params_,
+                params_ptr,
                 &[context.i32_type().const_int(i as u64, false)],
                 "",
             )
@@ -843,7 +1249,7 @@ pub(crate) fn compile_fn_expr<'ctx>(
         value = builder.build_load(base, "");
         target = unsafe {
             builder.build_in_bounds_gep(
-                this_env,
+                env_value,
                 &[context.i32_type().const_int((i + 1) as u64, false)],
                 "",
             )
@@ -851,25 +1257,14 @@ pub(crate) fn compile_fn_expr<'ctx>(
         builder.build_store(target, value);
     }
 
-    if is_expression {
-        let result = compile_expr(
-            es_node.get("body").unwrap(),
-            Rc::new(env),
-            context,
-            module,
-            builder,
-            &fun,
-        )?;
-        builder.build_return(Some(&result));
-    } else {
-        compile_block_stmt(
-            es_node.get("body").unwrap(),
-            Rc::new(env),
-            context,
-            module,
-            builder,
-            &fun,
-        )?;
+    match body {
+        FunctionBody::Expression(expr) => {
+            let result = compile_tail_expr(expr, Rc::new(env), context, module, builder, &fun)?;
+            builder.build_return(Some(&result));
+        }
+        FunctionBody::Block(block) => {
+            compile_block_stmt(block, Rc::new(env), context, module, builder, &fun)?;
+        }
     }
 
     if builder
@@ -878,8 +1273,7 @@ pub(crate) fn compile_fn_expr<'ctx>(
         .get_terminator()
         .is_none()
     {
-        let result = build_undefined(context, module, builder)?;
-        builder.build_return(Some(&result));
+        builder.build_return(Some(&build_undefined(context)));
     }
 
     builder.position_at_end(resume_point);
@@ -887,11 +1281,13 @@ pub(crate) fn compile_fn_expr<'ctx>(
     let closure_type = module.get_struct_type("closure").unwrap();
     let closure_ptr_type = closure_type.ptr_type(AddressSpace::Generic);
 
-    let mem = malloc(BOXED_VALUE_SIZE, context, module, builder, "")?;
+    let size = size_of(&closure_type, module);
+    let mem = gc_alloc(GC_VALUE, size, context, module, builder, "")?;
 
     let zero = context.i32_type().const_int(0, false);
     let one = context.i32_type().const_int(1, false);
     let two = context.i32_type().const_int(2, false);
+    let three = context.i32_type().const_int(3, false);
 
     let literal = builder
         .build_bitcast(mem, closure_ptr_type, "")
@@ -899,12 +1295,12 @@ pub(crate) fn compile_fn_expr<'ctx>(
     let type_ptr = unsafe { builder.build_in_bounds_gep(literal, &[zero, zero], "") };
     let env_ptr = unsafe { builder.build_in_bounds_gep(literal, &[zero, one], "") };
     let fun_ptr = unsafe { builder.build_in_bounds_gep(literal, &[zero, two], "") };
+    let arity_ptr = unsafe { builder.build_in_bounds_gep(literal, &[zero, three], "") };
 
-    builder.build_store(type_ptr, context.i64_type().const_int(3, false));
+    builder.build_store(type_ptr, i64_type.const_int(3, false));
     builder.build_store(env_ptr, *parent.ptr.clone().unwrap());
     builder.build_store(fun_ptr, fun.as_global_value());
+    builder.build_store(arity_ptr, i64_type.const_int(params.len() as u64, false));
 
-    Ok(builder
-        .build_bitcast(literal, source_obj_ptr_type, "")
-        .into_pointer_value())
+    Ok(build_box(literal, context, builder))
 }
@@ -0,0 +1,588 @@
+//! A mark-sweep garbage collector for the runtime heap.
+//!
+//! Every object is preceded by a `gc_header` and linked into a list of all
+//! objects, which is swept after marking everything reachable from the shadow
+//! stack `gc_roots` of values. Each allocation is pushed onto the shadow stack,
+//! and a call site pops everything its callee pushed except for the return
+//! value. Calls are nested at most `MAX_CALL_DEPTH` deep, well before the
+//! shadow stack fills up, but filling it is the same runtime error "Maximum
+//! call stack size exceeded.".
+
+use crate::error::CompileError;
+use crate::helper::{
+    build_error, build_undefined, size_of, size_type, POINTER_BITS, STACK_OVERFLOW_ERROR,
+};
+use inkwell::{
+    builder::Builder, context::Context, module::Module, values::IntValue, AddressSpace,
+    IntPredicate,
+};
+
+/// Objects without pointers to other objects, i.e. characters of strings.
+pub(crate) const GC_RAW: u64 = 0;
+/// Objects starting with a tag, i.e. strings, `closure`, `tail_call` and
+/// `pair`.
+pub(crate) const GC_VALUE: u64 = 1;
+/// Arrays of values, i.e. frames and params.
+pub(crate) const GC_SLOTS: u64 = 2;
+
+const ROOTS_CAPACITY: u32 = 1 << 20;
+// Collect once this many bytes have been allocated since the last collection,
+// or twice the live bytes after it if that is more.
+const INITIAL_THRESHOLD: u64 = 1 << 20;
+// The number of objects the mark stack first has room for, doubling whenever
+// it is full.
+const INITIAL_MARK_STACK_CAPACITY: u64 = 1 << 10;
+
+pub(crate) fn setup<'ctx>(
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+) -> Result<(), CompileError> {
+    let i8_type = context.i8_type();
+    let i8_ptr_type = i8_type.ptr_type(AddressSpace::Generic);
+    let i8_ptr_ptr_type = i8_ptr_type.ptr_type(AddressSpace::Generic);
+    let i32_type = context.i32_type();
+    let size_type = size_type(context, module);
+    let i64_type = context.i64_type();
+    let i64_ptr_type = i64_type.ptr_type(AddressSpace::Generic);
+    let void_type = context.void_type();
+
+    let closure_ptr_type = module
+        .get_struct_type("closure")
+        .unwrap()
+        .ptr_type(AddressSpace::Generic);
+    let tail_call_ptr_type = module
+        .get_struct_type("tail_call")
+        .unwrap()
+        .ptr_type(AddressSpace::Generic);
+    let pair_ptr_type = module
+        .get_struct_type("pair")
+        .unwrap()
+        .ptr_type(AddressSpace::Generic);
+
+    // next, kind, marked, size
+    let header_type = context.opaque_struct_type("gc_header");
+    let header_ptr_type = header_type.ptr_type(AddressSpace::Generic);
+    header_type.set_body(
+        &[
+            header_ptr_type.into(),
+            i32_type.into(),
+            i32_type.into(),
+            size_type.into(),
+        ],
+        false,
+    );
+    let header_size = size_type.const_int(size_of(&header_type, module), false);
+
+    let objects = module.add_global(header_ptr_type, None, "gc_objects");
+    objects.set_initializer(&header_ptr_type.const_null());
+    let allocated = module.add_global(size_type, None, "gc_allocated");
+    allocated.set_initializer(&size_type.const_zero());
+    let threshold = module.add_global(size_type, None, "gc_threshold");
+    threshold.set_initializer(&size_type.const_int(INITIAL_THRESHOLD, false));
+    let roots_type = i64_type.array_type(ROOTS_CAPACITY);
+    let roots = module.add_global(roots_type, None, "gc_roots");
+    roots.set_initializer(&roots_type.const_zero());
+    let roots_top = module.add_global(i32_type, None, "gc_roots_top");
+    roots_top.set_initializer(&i32_type.const_zero());
+    // the objects marked while marking, but not yet what they point to
+    let mark_stack = module.add_global(i8_ptr_ptr_type, None, "gc_mark_stack");
+    mark_stack.set_initializer(&i8_ptr_ptr_type.const_null());
+    let mark_stack_capacity = module.add_global(size_type, None, "gc_mark_stack_capacity");
+    mark_stack_capacity.set_initializer(&size_type.const_zero());
+    let mark_stack_top = module.add_global(size_type, None, "gc_mark_stack_top");
+    mark_stack_top.set_initializer(&size_type.const_zero());
+
+    let free_type = void_type.fn_type(&[i8_ptr_type.into()], false);
+    module.add_function("free", free_type, None);
+
+    let memset_type = i8_ptr_type.fn_type(
+        &[i8_ptr_type.into(), i32_type.into(), size_type.into()],
+        false,
+    );
+    module.add_function("memset", memset_type, None);
+
+    let realloc_type = i8_ptr_type.fn_type(&[i8_ptr_type.into(), size_type.into()], false);
+    module.add_function("realloc", realloc_type, None);
+
+    let _0 = i32_type.const_int(0, false);
+    let _1 = i32_type.const_int(1, false);
+    let _2 = i32_type.const_int(2, false);
+    let _3 = i32_type.const_int(3, false);
+
+    // push root fn
+    {
+        let push_root_fn_type = void_type.fn_type(&[i64_type.into()], false);
+        let push_root_fn = module.add_function("gc_push_root", push_root_fn_type, None);
+
+        let entry = context.append_basic_block(push_root_fn, "entry");
+        let overflow_block = context.append_basic_block(push_root_fn, "overflow");
+        let push_block = context.append_basic_block(push_root_fn, "push");
+        builder.position_at_end(entry);
+
+        let obj = push_root_fn.get_first_param().unwrap();
+        let top = builder
+            .build_load(roots_top.as_pointer_value(), "")
+            .into_int_value();
+        let capacity = i32_type.const_int(ROOTS_CAPACITY as u64, false);
+        let is_full = builder.build_int_compare(IntPredicate::UGE, top, capacity, "");
+        builder.build_conditional_branch(is_full, overflow_block, push_block);
+
+        // calls are limited to MAX_CALL_DEPTH well before the shadow stack fills
+        // up, so this is only a safety net
+        builder.position_at_end(overflow_block);
+        let undefined = build_undefined(context);
+        build_error(
+            STACK_OVERFLOW_ERROR,
+            undefined,
+            undefined,
+            None,
+            context,
+            module,
+            builder,
+        );
+        builder.build_unreachable();
+
+        builder.position_at_end(push_block);
+        let slot = unsafe { builder.build_in_bounds_gep(roots.as_pointer_value(), &[_0, top], "") };
+        builder.build_store(slot, obj);
+        let new_top = builder.build_int_add(top, _1, "");
+        builder.build_store(roots_top.as_pointer_value(), new_top);
+        builder.build_return(None);
+    }
+
+    // mark push fn, marking an object and pushing it onto the mark stack to
+    // mark what it points to later, unless it is null or already marked
+    {
+        let mark_push_fn_type = void_type.fn_type(&[i8_ptr_type.into()], false);
+        let mark_push_fn = module.add_function("gc_mark_push", mark_push_fn_type, None);
+
+        let realloc_fn = module.get_function("realloc").unwrap();
+
+        let entry = context.append_basic_block(mark_push_fn, "entry");
+        let check_block = context.append_basic_block(mark_push_fn, "check");
+        let mark_block = context.append_basic_block(mark_push_fn, "mark");
+        let grow_block = context.append_basic_block(mark_push_fn, "grow");
+        let push_block = context.append_basic_block(mark_push_fn, "push");
+        let end_block = context.append_basic_block(mark_push_fn, "end");
+
+        builder.position_at_end(entry);
+        let obj = mark_push_fn.get_first_param().unwrap().into_pointer_value();
+        let is_null = builder.build_is_null(obj, "");
+        builder.build_conditional_branch(is_null, end_block, check_block);
+
+        builder.position_at_end(check_block);
+        let header = builder
+            .build_bitcast(obj, header_ptr_type, "")
+            .into_pointer_value();
+        let header = unsafe {
+            builder.build_in_bounds_gep(header, &[i32_type.const_int(-1i64 as u64, true)], "")
+        };
+        let marked_ptr = unsafe { builder.build_in_bounds_gep(header, &[_0, _2], "") };
+        let marked = builder.build_load(marked_ptr, "").into_int_value();
+        let is_marked = builder.build_int_compare(IntPredicate::NE, marked, _0, "");
+        builder.build_conditional_branch(is_marked, end_block, mark_block);
+
+        // as objects are marked when pushed, each is pushed at most once
+        builder.position_at_end(mark_block);
+        builder.build_store(marked_ptr, _1);
+        let top = builder
+            .build_load(mark_stack_top.as_pointer_value(), "")
+            .into_int_value();
+        let capacity = builder
+            .build_load(mark_stack_capacity.as_pointer_value(), "")
+            .into_int_value();
+        let is_full = builder.build_int_compare(IntPredicate::EQ, top, capacity, "");
+        builder.build_conditional_branch(is_full, grow_block, push_block);
+
+        builder.position_at_end(grow_block);
+        let is_empty =
+            builder.build_int_compare(IntPredicate::EQ, capacity, size_type.const_zero(), "");
+        let doubled = builder.build_int_mul(capacity, size_type.const_int(2, false), "");
+        let new_capacity = builder
+            .build_select(
+                is_empty,
+                size_type.const_int(INITIAL_MARK_STACK_CAPACITY, false),
+                doubled,
+                "",
+            )
+            .into_int_value();
+        let pointer_size = size_type.const_int(size_of(&i8_ptr_type, module), false);
+        let new_size = builder.build_int_mul(new_capacity, pointer_size, "");
+        let stack = builder.build_load(mark_stack.as_pointer_value(), "");
+        let stack = builder.build_bitcast(stack, i8_ptr_type, "");
+        let new_stack = builder
+            .build_call(realloc_fn, &[stack, new_size.into()], "")
+            .try_as_basic_value()
+            .left()
+            .unwrap();
+        let new_stack = builder.build_bitcast(new_stack, i8_ptr_ptr_type, "");
+        builder.build_store(mark_stack.as_pointer_value(), new_stack);
+        builder.build_store(mark_stack_capacity.as_pointer_value(), new_capacity);
+        builder.build_unconditional_branch(push_block);
+
+        builder.position_at_end(push_block);
+        let stack = builder
+            .build_load(mark_stack.as_pointer_value(), "")
+            .into_pointer_value();
+        let slot = unsafe { builder.build_in_bounds_gep(stack, &[top], "") };
+        builder.build_store(slot, obj);
+        let new_top = builder.build_int_add(top, size_type.const_int(1, false), "");
+        builder.build_store(mark_stack_top.as_pointer_value(), new_top);
+        builder.build_unconditional_branch(end_block);
+
+        builder.position_at_end(end_block);
+        builder.build_return(None);
+    }
+
+    let mark_fn_type = void_type.fn_type(&[i8_ptr_type.into()], false);
+    let mark_fn = module.add_function("gc_mark", mark_fn_type, None);
+
+    // mark value fn, marking the object of a value if it is a heap object
+    {
+        let mark_value_fn_type = void_type.fn_type(&[i64_type.into()], false);
+        let mark_value_fn = module.add_function("gc_mark_value", mark_value_fn_type, None);
+
+        let entry = context.append_basic_block(mark_value_fn, "entry");
+        let pointer_block = context.append_basic_block(mark_value_fn, "pointer");
+        let end_block = context.append_basic_block(mark_value_fn, "end");
+
+        builder.position_at_end(entry);
+        let value = mark_value_fn.get_first_param().unwrap().into_int_value();
+        let pointer_bits = i64_type.const_int(POINTER_BITS, false);
+        let masked = builder.build_and(value, pointer_bits, "");
+        let is_pointer = builder.build_int_compare(IntPredicate::EQ, masked, pointer_bits, "");
+        builder.build_conditional_branch(is_pointer, pointer_block, end_block);
+
+        builder.position_at_end(pointer_block);
+        let address = builder.build_and(value, i64_type.const_int(!POINTER_BITS, false), "");
+        let obj = builder.build_int_to_ptr(address, i8_ptr_type, "");
+        builder.build_call(mark_fn, &[obj.into()], "");
+        builder.build_unconditional_branch(end_block);
+
+        builder.position_at_end(end_block);
+        builder.build_return(None);
+    }
+
+    // mark fn, marking everything reachable from an object. Rather than
+    // recursing into the objects it points to, which overflows the stack on
+    // long chains of frames and closures or deeply nested lists, they are
+    // pushed onto the mark stack, which is popped until it is empty.
+    {
+        let mark_push_fn = module.get_function("gc_mark_push").unwrap();
+        // pushes the object of a value, or null if it is not a heap object
+        let build_push_value = |value: IntValue<'ctx>| {
+            let pointer_bits = i64_type.const_int(POINTER_BITS, false);
+            let masked = builder.build_and(value, pointer_bits, "");
+            let is_pointer = builder.build_int_compare(IntPredicate::EQ, masked, pointer_bits, "");
+            let address = builder.build_and(value, i64_type.const_int(!POINTER_BITS, false), "");
+            let obj = builder.build_int_to_ptr(address, i8_ptr_type, "");
+            let obj = builder.build_select(is_pointer, obj, i8_ptr_type.const_null(), "");
+            builder.build_call(mark_push_fn, &[obj], "");
+        };
+
+        let entry = context.append_basic_block(mark_fn, "entry");
+        let loop_block = context.append_basic_block(mark_fn, "loop");
+        let pop_block = context.append_basic_block(mark_fn, "pop");
+        let value_block = context.append_basic_block(mark_fn, "value");
+        let string_block = context.append_basic_block(mark_fn, "string");
+        let closure_block = context.append_basic_block(mark_fn, "closure");
+        let tail_call_block = context.append_basic_block(mark_fn, "tail_call");
+        let pair_block = context.append_basic_block(mark_fn, "pair");
+        let slots_block = context.append_basic_block(mark_fn, "slots");
+        let slots_loop_block = context.append_basic_block(mark_fn, "slots.loop");
+        let slots_body_block = context.append_basic_block(mark_fn, "slots.body");
+        let end_block = context.append_basic_block(mark_fn, "end");
+
+        builder.position_at_end(entry);
+        let initial = mark_fn.get_first_param().unwrap();
+        builder.build_call(mark_push_fn, &[initial], "");
+        builder.build_unconditional_branch(loop_block);
+
+        builder.position_at_end(loop_block);
+        let top = builder
+            .build_load(mark_stack_top.as_pointer_value(), "")
+            .into_int_value();
+        let is_empty = builder.build_int_compare(IntPredicate::EQ, top, size_type.const_zero(), "");
+        builder.build_conditional_branch(is_empty, end_block, pop_block);
+
+        builder.position_at_end(pop_block);
+        let new_top = builder.build_int_sub(top, size_type.const_int(1, false), "");
+        builder.build_store(mark_stack_top.as_pointer_value(), new_top);
+        let stack = builder
+            .build_load(mark_stack.as_pointer_value(), "")
+            .into_pointer_value();
+        let slot = unsafe { builder.build_in_bounds_gep(stack, &[new_top], "") };
+        let obj = builder.build_load(slot, "").into_pointer_value();
+        let header = builder
+            .build_bitcast(obj, header_ptr_type, "")
+            .into_pointer_value();
+        let header = unsafe {
+            builder.build_in_bounds_gep(header, &[i32_type.const_int(-1i64 as u64, true)], "")
+        };
+        let kind_ptr = unsafe { builder.build_in_bounds_gep(header, &[_0, _1], "") };
+        let kind = builder.build_load(kind_ptr, "").into_int_value();
+        builder.build_switch(
+            kind,
+            loop_block,
+            &[
+                (i32_type.const_int(GC_VALUE, false), value_block),
+                (i32_type.const_int(GC_SLOTS, false), slots_block),
+            ],
+        );
+
+        builder.position_at_end(value_block);
+        let tag_ptr = builder
+            .build_bitcast(obj, i64_ptr_type, "")
+            .into_pointer_value();
+        let tag = builder.build_load(tag_ptr, "").into_int_value();
+        builder.build_switch(
+            tag,
+            loop_block,
+            &[
+                (i64_type.const_int(3, false), closure_block),
+                (i64_type.const_int(4, false), string_block),
+                (i64_type.const_int(5, false), tail_call_block),
+                (i64_type.const_int(6, false), pair_block),
+            ],
+        );
+
+        builder.position_at_end(string_block);
+        let payload_ptr = unsafe { builder.build_in_bounds_gep(tag_ptr, &[_1], "") };
+        let payload = builder.build_load(payload_ptr, "").into_int_value();
+        let string = builder.build_int_to_ptr(payload, i8_ptr_type, "");
+        builder.build_call(mark_push_fn, &[string.into()], "");
+        builder.build_unconditional_branch(loop_block);
+
+        builder.position_at_end(closure_block);
+        let closure = builder
+            .build_bitcast(obj, closure_ptr_type, "")
+            .into_pointer_value();
+        let env_ptr = unsafe { builder.build_in_bounds_gep(closure, &[_0, _1], "") };
+        let env = builder.build_load(env_ptr, "");
+        let env = builder.build_bitcast(env, i8_ptr_type, "");
+        builder.build_call(mark_push_fn, &[env], "");
+        builder.build_unconditional_branch(loop_block);
+
+        builder.position_at_end(tail_call_block);
+        let tail_call = builder
+            .build_bitcast(obj, tail_call_ptr_type, "")
+            .into_pointer_value();
+        for field in [_1, _2].iter() {
+            let field_ptr = unsafe { builder.build_in_bounds_gep(tail_call, &[_0, *field], "") };
+            let field_value = builder.build_load(field_ptr, "");
+            let field_value = builder.build_bitcast(field_value, i8_ptr_type, "");
+            builder.build_call(mark_push_fn, &[field_value], "");
+        }
+        builder.build_unconditional_branch(loop_block);
+
+        builder.position_at_end(pair_block);
+        let pair = builder
+            .build_bitcast(obj, pair_ptr_type, "")
+            .into_pointer_value();
+        for field in [_1, _2].iter() {
+            let field_ptr = unsafe { builder.build_in_bounds_gep(pair, &[_0, *field], "") };
+            let field_value = builder.build_load(field_ptr, "").into_int_value();
+            build_push_value(field_value);
+        }
+        builder.build_unconditional_branch(loop_block);
+
+        // the slots of frames include the parent frame in slot 0
+        builder.position_at_end(slots_block);
+        let size_ptr = unsafe { builder.build_in_bounds_gep(header, &[_0, _3], "") };
+        let size = builder.build_load(size_ptr, "").into_int_value();
+        let slot_size = size_type.const_int(size_of(&i64_type, module), false);
+        let count = builder.build_int_unsigned_div(size, slot_size, "");
+        let slots = builder
+            .build_bitcast(obj, i64_ptr_type, "")
+            .into_pointer_value();
+        builder.build_unconditional_branch(slots_loop_block);
+
+        builder.position_at_end(slots_loop_block);
+        let i = builder.build_phi(size_type, "i");
+        let i_value = i.as_basic_value().into_int_value();
+        let in_bounds = builder.build_int_compare(IntPredicate::ULT, i_value, count, "");
+        builder.build_conditional_branch(in_bounds, slots_body_block, loop_block);
+
+        builder.position_at_end(slots_body_block);
+        let slot_ptr = unsafe { builder.build_in_bounds_gep(slots, &[i_value], "") };
+        let slot = builder.build_load(slot_ptr, "").into_int_value();
+        build_push_value(slot);
+        let next_i = builder.build_int_add(i_value, size_type.const_int(1, false), "");
+        builder.build_unconditional_branch(slots_loop_block);
+        i.add_incoming(&[
+            (&size_type.const_zero(), slots_block),
+            (&next_i, slots_body_block),
+        ]);
+
+        builder.position_at_end(end_block);
+        builder.build_return(None);
+    }
+
+    // collect fn
+    {
+        let collect_fn_type = void_type.fn_type(&[], false);
+        let collect_fn = module.add_function("gc_collect", collect_fn_type, None);
+
+        let mark_value_fn = module.get_function("gc_mark_value").unwrap();
+        let free_fn = module.get_function("free").unwrap();
+
+        let entry = context.append_basic_block(collect_fn, "entry");
+        let roots_loop_block = context.append_basic_block(collect_fn, "roots.loop");
+        let roots_body_block = context.append_basic_block(collect_fn, "roots.body");
+        let sweep_block = context.append_basic_block(collect_fn, "sweep");
+        let sweep_check_block = context.append_basic_block(collect_fn, "sweep.check");
+        let live_block = context.append_basic_block(collect_fn, "live");
+        let dead_block = context.append_basic_block(collect_fn, "dead");
+        let end_block = context.append_basic_block(collect_fn, "end");
+
+        builder.position_at_end(entry);
+        let top = builder
+            .build_load(roots_top.as_pointer_value(), "")
+            .into_int_value();
+        builder.build_unconditional_branch(roots_loop_block);
+
+        builder.position_at_end(roots_loop_block);
+        let i = builder.build_phi(i32_type, "i");
+        let i_value = i.as_basic_value().into_int_value();
+        let in_bounds = builder.build_int_compare(IntPredicate::ULT, i_value, top, "");
+        builder.build_conditional_branch(in_bounds, roots_body_block, sweep_block);
+
+        builder.position_at_end(roots_body_block);
+        let root_ptr =
+            unsafe { builder.build_in_bounds_gep(roots.as_pointer_value(), &[_0, i_value], "") };
+        let root = builder.build_load(root_ptr, "");
+        builder.build_call(mark_value_fn, &[root], "");
+        let next_i = builder.build_int_add(i_value, _1, "");
+        builder.build_unconditional_branch(roots_loop_block);
+        i.add_incoming(&[(&_0, entry), (&next_i, roots_body_block)]);
+
+        // walk the list of objects through the link pointing to the current one,
+        // so that unreachable objects can be unlinked
+        builder.position_at_end(sweep_block);
+        builder.build_unconditional_branch(sweep_check_block);
+
+        builder.position_at_end(sweep_check_block);
+        let link = builder.build_phi(header_ptr_type.ptr_type(AddressSpace::Generic), "link");
+        let live = builder.build_phi(size_type, "live");
+        let link_value = link.as_basic_value().into_pointer_value();
+        let live_value = live.as_basic_value().into_int_value();
+        let current = builder.build_load(link_value, "").into_pointer_value();
+        let is_end = builder.build_is_null(current, "");
+        let marked_ptr = unsafe { builder.build_in_bounds_gep(current, &[_0, _2], "") };
+        let next_ptr = unsafe { builder.build_in_bounds_gep(current, &[_0, _0], "") };
+        let size_ptr = unsafe { builder.build_in_bounds_gep(current, &[_0, _3], "") };
+        let is_marked_block = context.append_basic_block(collect_fn, "sweep.marked");
+        builder.build_conditional_branch(is_end, end_block, is_marked_block);
+
+        builder.position_at_end(is_marked_block);
+        let marked = builder.build_load(marked_ptr, "").into_int_value();
+        let is_marked = builder.build_int_compare(IntPredicate::NE, marked, _0, "");
+        builder.build_conditional_branch(is_marked, live_block, dead_block);
+
+        builder.position_at_end(live_block);
+        builder.build_store(marked_ptr, _0);
+        let size = builder.build_load(size_ptr, "").into_int_value();
+        let size = builder.build_int_add(size, header_size, "");
+        let new_live = builder.build_int_add(live_value, size, "");
+        builder.build_unconditional_branch(sweep_check_block);
+
+        builder.position_at_end(dead_block);
+        let next = builder.build_load(next_ptr, "");
+        builder.build_store(link_value, next);
+        let current_as_i8_ptr = builder.build_bitcast(current, i8_ptr_type, "");
+        builder.build_call(free_fn, &[current_as_i8_ptr], "");
+        builder.build_unconditional_branch(sweep_check_block);
+
+        link.add_incoming(&[
+            (&objects.as_pointer_value(), sweep_block),
+            (&next_ptr, live_block),
+            (&link_value, dead_block),
+        ]);
+        live.add_incoming(&[
+            (&size_type.const_zero(), sweep_block),
+            (&new_live, live_block),
+            (&live_value, dead_block),
+        ]);
+
+        builder.position_at_end(end_block);
+        builder.build_store(allocated.as_pointer_value(), live_value);
+        let doubled = builder.build_int_mul(live_value, size_type.const_int(2, false), "");
+        let initial_threshold = size_type.const_int(INITIAL_THRESHOLD, false);
+        let is_above_initial =
+            builder.build_int_compare(IntPredicate::UGT, doubled, initial_threshold, "");
+        let new_threshold = builder.build_select(is_above_initial, doubled, initial_threshold, "");
+        builder.build_store(threshold.as_pointer_value(), new_threshold);
+        builder.build_return(None);
+    }
+
+    // alloc fn, returning zeroed memory for an object of the given kind
+    {
+        let alloc_fn_type = i8_ptr_type.fn_type(&[i32_type.into(), size_type.into()], false);
+        let alloc_fn = module.add_function("gc_alloc", alloc_fn_type, None);
+
+        let malloc_fn = module.get_function("malloc").unwrap();
+        let memset_fn = module.get_function("memset").unwrap();
+        let collect_fn = module.get_function("gc_collect").unwrap();
+        let push_root_fn = module.get_function("gc_push_root").unwrap();
+
+        let entry = context.append_basic_block(alloc_fn, "entry");
+        let collect_block = context.append_basic_block(alloc_fn, "collect");
+        let alloc_block = context.append_basic_block(alloc_fn, "alloc");
+
+        builder.position_at_end(entry);
+        let kind = alloc_fn.get_nth_param(0).unwrap().into_int_value();
+        let size = alloc_fn.get_nth_param(1).unwrap().into_int_value();
+        let allocated_value = builder
+            .build_load(allocated.as_pointer_value(), "")
+            .into_int_value();
+        let threshold_value = builder
+            .build_load(threshold.as_pointer_value(), "")
+            .into_int_value();
+        let should_collect =
+            builder.build_int_compare(IntPredicate::UGT, allocated_value, threshold_value, "");
+        builder.build_conditional_branch(should_collect, collect_block, alloc_block);
+
+        builder.position_at_end(collect_block);
+        builder.build_call(collect_fn, &[], "");
+        builder.build_unconditional_branch(alloc_block);
+
+        builder.position_at_end(alloc_block);
+        let total_size = builder.build_int_add(size, header_size, "");
+        let mem = builder
+            .build_call(malloc_fn, &[total_size.into()], "")
+            .try_as_basic_value()
+            .left()
+            .unwrap();
+        builder.build_call(memset_fn, &[mem, _0.into(), total_size.into()], "");
+
+        let header = builder
+            .build_bitcast(mem, header_ptr_type, "")
+            .into_pointer_value();
+        let next_ptr = unsafe { builder.build_in_bounds_gep(header, &[_0, _0], "") };
+        let kind_ptr = unsafe { builder.build_in_bounds_gep(header, &[_0, _1], "") };
+        let size_ptr = unsafe { builder.build_in_bounds_gep(header, &[_0, _3], "") };
+        let head = builder.build_load(objects.as_pointer_value(), "");
+        builder.build_store(next_ptr, head);
+        builder.build_store(kind_ptr, kind);
+        builder.build_store(size_ptr, size);
+        builder.build_store(objects.as_pointer_value(), header);
+
+        let allocated_value = builder
+            .build_load(allocated.as_pointer_value(), "")
+            .into_int_value();
+        let new_allocated = builder.build_int_add(allocated_value, total_size, "");
+        builder.build_store(allocated.as_pointer_value(), new_allocated);
+
+        let obj = unsafe { builder.build_in_bounds_gep(header, &[_1], "") };
+        let obj = builder
+            .build_bitcast(obj, i8_ptr_type, "")
+            .into_pointer_value();
+        let obj_as_i64 = builder.build_ptr_to_int(obj, i64_type, "");
+        let root = builder.build_or(obj_as_i64, i64_type.const_int(POINTER_BITS, false), "");
+        builder.build_call(push_root_fn, &[root.into()], "");
+        builder.build_return(Some(&obj));
+    }
+
+    Ok(())
+}
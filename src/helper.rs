@@ -1,60 +1,135 @@
 use std::rc::Rc;
 
+use crate::ast::{SourceLocation, Stmt};
 use crate::env::Env;
-use anyhow::Error;
+use crate::error::CompileError;
+use crate::gc::{GC_RAW, GC_SLOTS, GC_VALUE};
 use inkwell::{
     builder::Builder,
     context::Context,
     module::Module,
-    values::{IntValue, PointerValue},
+    targets::TargetData,
+    types::{AnyType, IntType, PointerType},
+    values::{FloatValue, IntValue, PointerValue},
     AddressSpace,
 };
-use serde_json::Value;
 
-pub(crate) const BOXED_VALUE_SIZE: u64 = 16;
+// Values are NaN-boxed into an i64. Any double that is not one of the quiet
+// NaNs below is a number. Undefined, booleans and null are immediates, while
+// heap objects (strings, closures and pairs) have the sign bit set as well,
+// with the pointer in the lower bits. Every heap object starts with an i64
+// type tag.
+pub(crate) const QNAN: u64 = 0x7ffc_0000_0000_0000;
+pub(crate) const POINTER_BITS: u64 = 0xfffc_0000_0000_0000;
+pub(crate) const UNDEFINED: u64 = QNAN | 1;
+pub(crate) const FALSE: u64 = QNAN | 2;
+pub(crate) const TRUE: u64 = QNAN | 3;
+pub(crate) const NULL: u64 = QNAN | 4;
+
+// Kinds of runtime errors reported by the `error` runtime function.
+pub(crate) const UNARY_TYPE_ERROR: u64 = 0;
+pub(crate) const LEFT_TYPE_ERROR: u64 = 1;
+pub(crate) const RIGHT_TYPE_ERROR: u64 = 2;
+pub(crate) const CALL_NON_FUNCTION_ERROR: u64 = 3;
+pub(crate) const ARITY_ERROR: u64 = 4;
+pub(crate) const CONDITION_TYPE_ERROR: u64 = 5;
+pub(crate) const STACK_OVERFLOW_ERROR: u64 = 6;
+
+// The most calls other than tail calls that may be nested before the program
+// fails with "Maximum call stack size exceeded.". Each takes a frame on the
+// native stack, and the limit leaves 2 KiB of the default 8 MiB stack to each.
+pub(crate) const MAX_CALL_DEPTH: u64 = 4096;
+
+// The arity of closures taking any number of arguments, i.e. `list` used as a
+// value.
+pub(crate) const ANY_ARITY: u64 = u64::MAX;
+
+// Not a type of any value, but the expected type of operands of `+` and the
+// comparison operators.
+pub(crate) const STRING_OR_NUMBER: u64 = 100;
 
 pub(crate) fn allocate_env<'ctx>(
-    body: &[Value],
+    body: &[Stmt],
     parent: Option<Rc<Env<'ctx>>>,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
-) -> Result<Env<'ctx>, Error> {
-    let source_obj_type = module.get_struct_type("source_obj").unwrap();
-    let source_obj_ptr_type = source_obj_type.ptr_type(AddressSpace::Generic);
-    let source_obj_ptr_ptr_type = source_obj_ptr_type.ptr_type(AddressSpace::Generic);
-    let source_obj_ptr_ptr_ptr_type = source_obj_ptr_ptr_type.ptr_type(AddressSpace::Generic);
+) -> Result<Env<'ctx>, CompileError> {
+    let frame_type = context.i64_type().ptr_type(AddressSpace::Generic);
 
     let mut env = Env::new(parent.clone());
-    let env_size = (env.add_and_count_decls(body)? + 1) * 8;
-    let addr = malloc(env_size as u64, context, module, builder, "env")?;
+    let env_size = (env.add_and_count_decls(body)? + 1) * size_of(&context.i64_type(), module);
+    let addr = gc_alloc(GC_SLOTS, env_size, context, module, builder, "env")?;
     let env_value = builder
-        .build_bitcast(addr, source_obj_ptr_ptr_type, "")
+        .build_bitcast(addr, frame_type, "")
         .into_pointer_value();
     env.ptr = Some(Rc::new(env_value));
 
     if let Some(parent) = parent {
         let parent_addr = *parent.ptr.clone().unwrap();
-        let frame_ptr = builder
-            .build_bitcast(env_value, source_obj_ptr_ptr_ptr_type, "frame")
-            .into_pointer_value();
-        builder.build_store(frame_ptr, parent_addr);
+        let parent_value = build_box(parent_addr, context, builder);
+        builder.build_store(env_value, parent_value);
     }
 
     Ok(env)
 }
 
-pub(crate) fn malloc<'ctx>(
+/// Returns a pointer to the slot of `name` in the frames, following the
+/// parent frames stored in slot 0.
+pub(crate) fn build_slot_ptr<'ctx>(
+    name: &str,
+    env: &Env<'ctx>,
+    context: &'ctx Context,
+    builder: &Builder<'ctx>,
+) -> Result<PointerValue<'ctx>, CompileError> {
+    let frame_type = context.i64_type().ptr_type(AddressSpace::Generic);
+
+    let (jumps, offset) = env.lookup(name)?;
+    let mut frame = *env.ptr.clone().unwrap();
+
+    for _ in 0..jumps {
+        let parent = builder.build_load(frame, "").into_int_value();
+        frame = build_unbox(parent, frame_type, context, builder);
+    }
+
+    // SAFETY: Inherently unsafe
+    let ptr = unsafe {
+        builder.build_in_bounds_gep(frame, &[context.i32_type().const_int(offset, false)], "")
+    };
+
+    Ok(ptr)
+}
+
+/// Returns the layout of the module's target.
+pub(crate) fn target_data(module: &Module) -> TargetData {
+    TargetData::create(&module.get_data_layout().as_str().to_string_lossy())
+}
+
+/// Returns the number of bytes allocated for a value of the given type on the
+/// module's target.
+pub(crate) fn size_of<'ctx>(ty: &dyn AnyType<'ctx>, module: &Module<'ctx>) -> u64 {
+    target_data(module).get_abi_size(ty)
+}
+
+/// Returns the type of sizes on the module's target, i.e. C's `size_t`.
+pub(crate) fn size_type<'ctx>(context: &'ctx Context, module: &Module<'ctx>) -> IntType<'ctx> {
+    context.ptr_sized_int_type(&target_data(module), None)
+}
+
+/// Allocates zeroed memory for an object of the given GC kind, see `gc.rs`.
+pub(crate) fn gc_alloc<'ctx>(
+    kind: u64,
     size: u64,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
     name: &str,
-) -> Result<PointerValue<'ctx>, Error> {
-    let size_value = context.i32_type().const_int(size, false);
-    let malloc_fn = module.get_function("malloc").unwrap();
+) -> Result<PointerValue<'ctx>, CompileError> {
+    let kind_value = context.i32_type().const_int(kind, false);
+    let size_value = size_type(context, module).const_int(size, false);
+    let gc_alloc_fn = module.get_function("gc_alloc").unwrap();
     let call = builder
-        .build_call(malloc_fn, &[size_value.into()], name)
+        .build_call(gc_alloc_fn, &[kind_value.into(), size_value.into()], name)
         .try_as_basic_value()
         .left()
         .unwrap()
@@ -62,19 +137,142 @@ pub(crate) fn malloc<'ctx>(
     Ok(call)
 }
 
-pub(crate) fn build_literal<'ctx>(
-    obj_type: &IntValue,
-    obj_value: &IntValue,
+pub(crate) fn build_box<'ctx>(
+    ptr: PointerValue<'ctx>,
+    context: &'ctx Context,
+    builder: &Builder<'ctx>,
+) -> IntValue<'ctx> {
+    let i64_type = context.i64_type();
+    let ptr_as_i64 = builder.build_ptr_to_int(ptr, i64_type, "");
+    builder.build_or(ptr_as_i64, i64_type.const_int(POINTER_BITS, false), "")
+}
+
+pub(crate) fn build_unbox<'ctx>(
+    value: IntValue<'ctx>,
+    ptr_type: PointerType<'ctx>,
     context: &'ctx Context,
+    builder: &Builder<'ctx>,
+) -> PointerValue<'ctx> {
+    let i64_type = context.i64_type();
+    let ptr_as_i64 = builder.build_and(value, i64_type.const_int(!POINTER_BITS, false), "");
+    builder.build_int_to_ptr(ptr_as_i64, ptr_type, "")
+}
+
+/// Returns the type tag of a value, i.e. 0 for undefined, 1 for booleans, 2
+/// for numbers, 3 for functions, 4 for strings, 6 for pairs and 7 for null.
+pub(crate) fn build_type_of<'ctx>(
+    value: IntValue<'ctx>,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
-) -> Result<PointerValue<'ctx>, Error> {
-    let source_obj_ptr_type = module
-        .get_struct_type("source_obj")
+) -> IntValue<'ctx> {
+    let value_type_fn = module.get_function("value_type").unwrap();
+    builder
+        .build_call(value_type_fn, &[value.into()], "")
+        .try_as_basic_value()
+        .left()
         .unwrap()
-        .ptr_type(AddressSpace::Generic);
+        .into_int_value()
+}
+
+pub(crate) fn build_undefined<'ctx>(context: &'ctx Context) -> IntValue<'ctx> {
+    context.i64_type().const_int(UNDEFINED, false)
+}
+
+pub(crate) fn build_null<'ctx>(context: &'ctx Context) -> IntValue<'ctx> {
+    context.i64_type().const_int(NULL, false)
+}
+
+pub(crate) fn build_boolean<'ctx>(value: bool, context: &'ctx Context) -> IntValue<'ctx> {
+    context
+        .i64_type()
+        .const_int(if value { TRUE } else { FALSE }, false)
+}
+
+pub(crate) fn build_boolean_from_i1<'ctx>(
+    value: IntValue<'ctx>,
+    context: &'ctx Context,
+    builder: &Builder<'ctx>,
+) -> IntValue<'ctx> {
+    builder
+        .build_select(
+            value,
+            build_boolean(true, context),
+            build_boolean(false, context),
+            "",
+        )
+        .into_int_value()
+}
+
+/// Only the lowest bit of a boolean differs between true and false.
+pub(crate) fn build_boolean_value<'ctx>(
+    value: IntValue<'ctx>,
+    context: &'ctx Context,
+    builder: &Builder<'ctx>,
+) -> IntValue<'ctx> {
+    builder.build_int_truncate(value, context.bool_type(), "")
+}
 
-    let mem = malloc(BOXED_VALUE_SIZE, context, module, builder, "")?;
+pub(crate) fn build_number<'ctx>(value: f64, context: &'ctx Context) -> IntValue<'ctx> {
+    context.i64_type().const_int(value.to_bits(), false)
+}
+
+pub(crate) fn build_number_from_f64<'ctx>(
+    value: FloatValue<'ctx>,
+    context: &'ctx Context,
+    builder: &Builder<'ctx>,
+) -> IntValue<'ctx> {
+    builder
+        .build_bitcast(value, context.i64_type(), "")
+        .into_int_value()
+}
+
+pub(crate) fn build_number_value<'ctx>(
+    value: IntValue<'ctx>,
+    context: &'ctx Context,
+    builder: &Builder<'ctx>,
+) -> FloatValue<'ctx> {
+    builder
+        .build_bitcast(value, context.f64_type(), "")
+        .into_float_value()
+}
+
+pub(crate) fn build_string<'ctx>(
+    value: &str,
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+) -> Result<IntValue<'ctx>, CompileError> {
+    let literal_ptr = builder
+        .build_global_string_ptr(value, "str")
+        .as_pointer_value();
+    // copy the literal to the heap, as the GC can only handle heap strings
+    let size = value.len() as u64 + 1;
+    let string_ptr = gc_alloc(GC_RAW, size, context, module, builder, "")?;
+    let memcpy_fn = module.get_function("memcpy").unwrap();
+    builder.build_call(
+        memcpy_fn,
+        &[
+            string_ptr.into(),
+            literal_ptr.into(),
+            size_type(context, module).const_int(size, false).into(),
+        ],
+        "",
+    );
+    build_string_from_ptr(string_ptr, context, module, builder)
+}
+
+/// Wraps a heap-allocated C string into a string object.
+pub(crate) fn build_string_from_ptr<'ctx>(
+    string_ptr: PointerValue<'ctx>,
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+) -> Result<IntValue<'ctx>, CompileError> {
+    let source_obj_type = module.get_struct_type("source_obj").unwrap();
+    let source_obj_ptr_type = source_obj_type.ptr_type(AddressSpace::Generic);
+
+    let size = size_of(&source_obj_type, module);
+    let mem = gc_alloc(GC_VALUE, size, context, module, builder, "")?;
 
     let _0 = context.i32_type().const_int(0, false);
     let _1 = context.i32_type().const_int(1, false);
@@ -86,45 +284,109 @@ pub(crate) fn build_literal<'ctx>(
     let type_ptr = unsafe { builder.build_in_bounds_gep(obj_ptr, &[_0, _0], "") };
     let value_ptr = unsafe { builder.build_in_bounds_gep(obj_ptr, &[_0, _1], "") };
 
-    builder.build_store(type_ptr, *obj_type);
-    builder.build_store(value_ptr, *obj_value);
+    let string_value = builder.build_ptr_to_int(string_ptr, context.i64_type(), "");
+    builder.build_store(type_ptr, context.i64_type().const_int(4, false));
+    builder.build_store(value_ptr, string_value);
+
+    Ok(build_box(obj_ptr, context, builder))
+}
+
+/// Returns the C string of a string value.
+pub(crate) fn build_string_value<'ctx>(
+    value: IntValue<'ctx>,
+    context: &'ctx Context,
+    module: &Module<'ctx>,
+    builder: &Builder<'ctx>,
+) -> PointerValue<'ctx> {
+    let source_obj_ptr_type = module
+        .get_struct_type("source_obj")
+        .unwrap()
+        .ptr_type(AddressSpace::Generic);
 
-    Ok(obj_ptr)
+    let _0 = context.i32_type().const_int(0, false);
+    let _1 = context.i32_type().const_int(1, false);
+
+    let obj_ptr = build_unbox(value, source_obj_ptr_type, context, builder);
+    let value_ptr = unsafe { builder.build_in_bounds_gep(obj_ptr, &[_0, _1], "") };
+    let string_value = builder.build_load(value_ptr, "").into_int_value();
+    builder.build_int_to_ptr(
+        string_value,
+        context.i8_type().ptr_type(AddressSpace::Generic),
+        "",
+    )
 }
 
-pub(crate) fn build_undefined<'ctx>(
+pub(crate) fn build_pair<'ctx>(
+    head: IntValue<'ctx>,
+    tail: IntValue<'ctx>,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
-) -> Result<PointerValue<'ctx>, Error> {
-    let undefined_type = context.i64_type().const_int(0, false);
-    let undefined_value = context.i64_type().const_int(0, false);
-    build_literal(&undefined_type, &undefined_value, context, module, builder)
+) -> Result<IntValue<'ctx>, CompileError> {
+    let pair_type = module.get_struct_type("pair").unwrap();
+
+    let size = size_of(&pair_type, module);
+    let mem = gc_alloc(GC_VALUE, size, context, module, builder, "pair")?;
+    let pair_ptr = builder
+        .build_bitcast(mem, pair_type.ptr_type(AddressSpace::Generic), "")
+        .into_pointer_value();
+
+    let _0 = context.i32_type().const_int(0, false);
+    // SAFETY: Inherently unsafe
+    let type_ptr = unsafe { builder.build_in_bounds_gep(pair_ptr, &[_0, _0], "") };
+    builder.build_store(type_ptr, context.i64_type().const_int(6, false));
+    for (field, value) in [(1, head), (2, tail)].iter() {
+        let field = context.i32_type().const_int(*field, false);
+        let field_ptr = unsafe { builder.build_in_bounds_gep(pair_ptr, &[_0, field], "") };
+        builder.build_store(field_ptr, *value);
+    }
+
+    Ok(build_box(pair_ptr, context, builder))
 }
 
-pub(crate) fn build_boolean<'ctx>(
-    value: bool,
+/// Returns a pointer to the head or, if `tail` is set, the tail of a pair
+/// value.
+pub(crate) fn build_pair_field_ptr<'ctx>(
+    value: IntValue<'ctx>,
+    tail: bool,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
-) -> Result<PointerValue<'ctx>, Error> {
-    let bool_type = context.i64_type().const_int(1, false);
-    let bool_value = context
-        .i64_type()
-        .const_int(if value { 1 } else { 0 }, false);
-    build_literal(&bool_type, &bool_value, context, module, builder)
+) -> PointerValue<'ctx> {
+    let pair_ptr_type = module
+        .get_struct_type("pair")
+        .unwrap()
+        .ptr_type(AddressSpace::Generic);
+
+    let _0 = context.i32_type().const_int(0, false);
+    let field = context
+        .i32_type()
+        .const_int(if tail { 2 } else { 1 }, false);
+
+    let pair_ptr = build_unbox(value, pair_ptr_type, context, builder);
+    // SAFETY: Inherently unsafe
+    unsafe { builder.build_in_bounds_gep(pair_ptr, &[_0, field], "") }
 }
 
-pub(crate) fn build_number<'ctx>(
-    value: f64,
+pub(crate) fn build_error<'ctx>(
+    kind: u64,
+    a: IntValue<'ctx>,
+    b: IntValue<'ctx>,
+    loc: Option<SourceLocation>,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
-) -> Result<PointerValue<'ctx>, Error> {
-    let number_type = context.i64_type().const_int(2, false);
-    let number_value = context.f64_type().const_float(value);
-    let number_value_as_i64 = builder
-        .build_bitcast(number_value, context.i64_type(), "")
-        .into_int_value();
-    build_literal(&number_type, &number_value_as_i64, context, module, builder)
+) {
+    let error_fn = module.get_function("error").unwrap();
+    let i32_type = context.i32_type();
+    let kind = i32_type.const_int(kind, false);
+    // Line 0 tells the runtime that the location is unknown.
+    let (line, column) = loc.map_or((0, 0), |loc| (loc.start.line, loc.start.column));
+    let line = i32_type.const_int(line as u64, false);
+    let column = i32_type.const_int(column as u64, false);
+    builder.build_call(
+        error_fn,
+        &[kind.into(), a.into(), b.into(), line.into(), column.into()],
+        "",
+    );
 }
@@ -0,0 +1,690 @@
+//! A tree-walking interpreter for Source §1 and the pairs of §2, serving as a
+//! reference for the compiled code. It runs the prelude ahead of the program
+//! like `main` does, resolves names with the same `Env` as the code generator,
+//! keeps the values of each scope in a `Frame` like the compiled frames, and
+//! reports the runtime errors of the `error` runtime function with the same
+//! messages.
+
+use std::{cell::RefCell, cmp::Ordering, fmt, io::Write, rc::Rc};
+
+use crate::ast::{
+    BinaryExpression, BinaryOperator, BlockStatement, CallExpression, Expr, FunctionBody,
+    Identifier, LiteralValue, LogicalExpression, LogicalOperator, Program, SourceLocation, Stmt,
+    UnaryExpression, UnaryOperator,
+};
+use crate::checker::{find_builtin, is_builtin};
+use crate::env::Env;
+use crate::error::InterpretError;
+use crate::helper::MAX_CALL_DEPTH;
+
+#[derive(Clone)]
+enum Value<'a> {
+    Undefined,
+    Boolean(bool),
+    Number(f64),
+    String(Rc<str>),
+    Function(Rc<Closure<'a>>),
+    /// A built-in function used as a value, by its name.
+    Builtin(&'static str),
+    Null,
+    Pair(Rc<Pair<'a>>),
+}
+
+struct Pair<'a> {
+    head: RefCell<Value<'a>>,
+    tail: RefCell<Value<'a>>,
+}
+
+struct Closure<'a> {
+    params: &'a [Identifier],
+    body: &'a FunctionBody,
+    env: Rc<Env<'static>>,
+    frame: Rc<Frame<'a>>,
+}
+
+struct Frame<'a> {
+    // slot 0 is unused, as it holds the parent frame in the compiled code
+    slots: RefCell<Vec<Value<'a>>>,
+    parent: Option<Rc<Frame<'a>>>,
+}
+
+/// The result of evaluating an expression in tail position, where calls are
+/// returned to the caller like in the compiled code, so that iterative
+/// processes run in constant stack.
+enum Completion<'a> {
+    Value(Value<'a>),
+    TailCall(Rc<Closure<'a>>, Vec<Value<'a>>),
+    /// A call of a built-in function used as a value, which is made through
+    /// its closure in the compiled code.
+    BuiltinCall(&'static str, Vec<Value<'a>>),
+}
+
+impl<'a> Value<'a> {
+    fn pair(head: Value<'a>, tail: Value<'a>) -> Self {
+        Value::Pair(Rc::new(Pair {
+            head: RefCell::new(head),
+            tail: RefCell::new(tail),
+        }))
+    }
+
+    fn type_name(&self) -> &'static str {
+        match self {
+            Value::Undefined => "undefined",
+            Value::Boolean(_) => "boolean",
+            Value::Number(_) => "number",
+            Value::Function(_) | Value::Builtin(_) => "function",
+            Value::String(_) => "string",
+            Value::Pair(_) => "pair",
+            Value::Null => "null",
+        }
+    }
+}
+
+/// Formats a value like the `print_value` runtime function.
+impl<'a> fmt::Display for Value<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Undefined => write!(f, "undefined"),
+            Value::Boolean(value) => write!(f, "{}", value),
+            Value::Number(value) => write!(f, "{}", number_to_string(*value)),
+            Value::Function(_) | Value::Builtin(_) => write!(f, "Function"),
+            Value::String(value) => write!(f, "\"{}\"", value),
+            Value::Null => write!(f, "null"),
+            // the tails of lists are written in a loop like in `print_value`
+            Value::Pair(pair) => {
+                let mut pair = pair.clone();
+                let mut depth = 1;
+                loop {
+                    write!(f, "[{}, ", pair.head.borrow())?;
+                    let tail = pair.tail.borrow().clone();
+                    match tail {
+                        Value::Pair(tail) => {
+                            pair = tail;
+                            depth += 1;
+                        }
+                        tail => break write!(f, "{}{}", tail, "]".repeat(depth)),
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Frame<'a> {
+    fn new(count: u64, parent: Option<Rc<Frame<'a>>>) -> Self {
+        Frame {
+            slots: RefCell::new(vec![Value::Undefined; count as usize + 1]),
+            parent,
+        }
+    }
+}
+
+/// Formats a number like ECMAScript's Number::toString, see the
+/// `number_to_string` runtime function.
+fn number_to_string(x: f64) -> String {
+    if x.is_nan() {
+        return "NaN".into();
+    } else if x == 0.0 {
+        return "0".into();
+    } else if x.is_infinite() {
+        return if x > 0.0 { "Infinity" } else { "-Infinity" }.into();
+    }
+
+    let sign = if x < 0.0 { "-" } else { "" };
+    // the shortest digits that round-trip, as d.ddde[-]n
+    let scientific = format!("{:e}", x.abs());
+    let (mantissa, exponent) = scientific.split_at(scientific.find('e').unwrap());
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let k = digits.len() as i32;
+    let n = exponent[1..].parse::<i32>().unwrap() + 1;
+
+    if k <= n && n <= 21 {
+        format!("{}{}{}", sign, digits, "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        let (integer, fraction) = digits.split_at(n as usize);
+        format!("{}{}.{}", sign, integer, fraction)
+    } else if -6 < n && n <= 0 {
+        format!("{}0.{}{}", sign, "0".repeat(-n as usize), digits)
+    } else {
+        let (first, rest) = digits.split_at(1);
+        let dot = if rest.is_empty() { "" } else { "." };
+        let exponent_sign = if n - 1 < 0 { '-' } else { '+' };
+        let exponent = (n - 1).abs();
+        format!(
+            "{}{}{}{}e{}{}",
+            sign, first, dot, rest, exponent_sign, exponent
+        )
+    }
+}
+
+fn runtime_error(loc: Option<SourceLocation>, message: String) -> InterpretError {
+    InterpretError::Runtime { message, loc }
+}
+
+fn type_error(
+    loc: Option<SourceLocation>,
+    side: &str,
+    expected: &str,
+    actual: &Value,
+) -> InterpretError {
+    runtime_error(
+        loc,
+        format!("Expected {}{}, got {}.", expected, side, actual.type_name()),
+    )
+}
+
+const LEFT: &str = " on left hand side of operation";
+const RIGHT: &str = " on right hand side of operation";
+
+pub(crate) fn interpret(
+    program: &Program,
+    prelude: &Program,
+    out: &mut dyn Write,
+) -> Result<(), InterpretError> {
+    Interpreter { out, depth: 0 }.program(program, prelude)
+}
+
+struct Interpreter<'w> {
+    out: &'w mut dyn Write,
+    /// The number of calls being made other than tail calls, like the
+    /// `call_depth` of the compiled code.
+    depth: u64,
+}
+
+impl<'w> Interpreter<'w> {
+    fn program<'a>(
+        &mut self,
+        program: &'a Program,
+        prelude: &'a Program,
+    ) -> Result<(), InterpretError> {
+        // the prelude's frame is the parent of the program's top-level frame
+        let mut prelude_env = Env::new(None);
+        let count = prelude_env.add_and_count_decls(&prelude.body)?;
+        let prelude_env = Rc::new(prelude_env);
+        let prelude_frame = Rc::new(Frame::new(count, None));
+        for stmt in prelude.body.iter() {
+            self.stmt(stmt, &prelude_env, &prelude_frame)?;
+        }
+
+        let mut env = Env::new(Some(prelude_env));
+        let count = env.add_and_count_decls(&program.body)?;
+        let env = Rc::new(env);
+        let frame = Rc::new(Frame::new(count, Some(prelude_frame)));
+
+        // like `main`, display the value of the last statement
+        let mut last = Value::Undefined;
+        for stmt in program.body.iter() {
+            last = match stmt {
+                Stmt::ExpressionStatement(stmt) => self.expr(&stmt.expression, &env, &frame)?,
+                _ => {
+                    self.stmt(stmt, &env, &frame)?;
+                    Value::Undefined
+                }
+            };
+        }
+        writeln!(self.out, "{}", last)?;
+
+        Ok(())
+    }
+
+    /// Returns the completion of a return statement, if one was executed.
+    fn stmt<'a>(
+        &mut self,
+        stmt: &'a Stmt,
+        env: &Rc<Env<'static>>,
+        frame: &Rc<Frame<'a>>,
+    ) -> Result<Option<Completion<'a>>, InterpretError> {
+        match stmt {
+            Stmt::VariableDeclaration(decl) => {
+                let declaration = &decl.declarations[0];
+                let value = self.expr(&declaration.init, env, frame)?;
+                self.assign(&declaration.id.name, value, env, frame)?;
+                Ok(None)
+            }
+            Stmt::FunctionDeclaration(decl) => {
+                let value = self.closure(&decl.params, &decl.body, env, frame);
+                self.assign(&decl.id.name, value, env, frame)?;
+                Ok(None)
+            }
+            Stmt::ExpressionStatement(stmt) => {
+                self.expr(&stmt.expression, env, frame)?;
+                Ok(None)
+            }
+            Stmt::BlockStatement(block) => self.block(block, env, frame),
+            Stmt::IfStatement(stmt) => {
+                let branch = if self.condition(&stmt.test, stmt.loc, env, frame)? {
+                    &stmt.consequent
+                } else {
+                    &stmt.alternate
+                };
+                self.stmt(branch, env, frame)
+            }
+            Stmt::ReturnStatement(stmt) => self.tail_expr(&stmt.argument, env, frame).map(Some),
+        }
+    }
+
+    fn block<'a>(
+        &mut self,
+        block: &'a BlockStatement,
+        parent_env: &Rc<Env<'static>>,
+        parent_frame: &Rc<Frame<'a>>,
+    ) -> Result<Option<Completion<'a>>, InterpretError> {
+        let mut env = Env::new(Some(parent_env.clone()));
+        let count = env.add_and_count_decls(&block.body)?;
+        let env = Rc::new(env);
+        let frame = Rc::new(Frame::new(count, Some(parent_frame.clone())));
+
+        for stmt in block.body.iter() {
+            if let Some(completion) = self.stmt(stmt, &env, &frame)? {
+                return Ok(Some(completion));
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn expr<'a>(
+        &mut self,
+        expr: &'a Expr,
+        env: &Rc<Env<'static>>,
+        frame: &Rc<Frame<'a>>,
+    ) -> Result<Value<'a>, InterpretError> {
+        match expr {
+            Expr::Identifier(id) => self.lookup(id, env, frame),
+            Expr::Literal(lit) => Ok(match &lit.value {
+                LiteralValue::Null => Value::Null,
+                LiteralValue::Boolean(value) => Value::Boolean(*value),
+                LiteralValue::Number(value) => Value::Number(*value),
+                LiteralValue::String(value) => Value::String(value.as_str().into()),
+            }),
+            Expr::UnaryExpression(expr) => self.unary(expr, env, frame),
+            Expr::BinaryExpression(expr) => self.binary(expr, env, frame),
+            Expr::LogicalExpression(expr) => self.logical(expr, env, frame),
+            Expr::CallExpression(expr) => match self.callee_and_args(expr, env, frame)? {
+                Completion::Value(value) => Ok(value),
+                // only calls other than tail calls nest, up to the same depth
+                // as in the compiled code
+                call => {
+                    if self.depth >= MAX_CALL_DEPTH {
+                        return Err(runtime_error(
+                            None,
+                            "Maximum call stack size exceeded.".into(),
+                        ));
+                    }
+                    self.depth += 1;
+                    let value = self.apply(call);
+                    self.depth -= 1;
+                    value
+                }
+            },
+            Expr::ConditionalExpression(expr) => {
+                let branch = if self.condition(&expr.test, expr.loc, env, frame)? {
+                    &expr.consequent
+                } else {
+                    &expr.alternate
+                };
+                self.expr(branch, env, frame)
+            }
+            Expr::ArrowFunctionExpression(expr) => {
+                Ok(self.closure(&expr.params, &expr.body, env, frame))
+            }
+            Expr::AssignmentExpression(_) => {
+                unreachable!("assignments are rejected by the checker")
+            }
+        }
+    }
+
+    fn tail_expr<'a>(
+        &mut self,
+        expr: &'a Expr,
+        env: &Rc<Env<'static>>,
+        frame: &Rc<Frame<'a>>,
+    ) -> Result<Completion<'a>, InterpretError> {
+        match expr {
+            Expr::CallExpression(expr) => self.callee_and_args(expr, env, frame),
+            Expr::ConditionalExpression(expr) => {
+                let branch = if self.condition(&expr.test, expr.loc, env, frame)? {
+                    &expr.consequent
+                } else {
+                    &expr.alternate
+                };
+                self.tail_expr(branch, env, frame)
+            }
+            _ => self.expr(expr, env, frame).map(Completion::Value),
+        }
+    }
+
+    fn lookup<'a>(
+        &mut self,
+        id: &Identifier,
+        env: &Rc<Env<'static>>,
+        frame: &Rc<Frame<'a>>,
+    ) -> Result<Value<'a>, InterpretError> {
+        if id.name == "undefined" {
+            return Ok(Value::Undefined);
+        }
+        if let (Some((name, _)), Err(_)) = (find_builtin(&id.name), env.lookup(&id.name)) {
+            return Ok(Value::Builtin(name));
+        }
+
+        let (frame, offset) = self.slot(&id.name, env, frame)?;
+        let value = frame.slots.borrow()[offset].clone();
+        Ok(value)
+    }
+
+    fn assign<'a>(
+        &mut self,
+        name: &str,
+        value: Value<'a>,
+        env: &Rc<Env<'static>>,
+        frame: &Rc<Frame<'a>>,
+    ) -> Result<(), InterpretError> {
+        let (frame, offset) = self.slot(name, env, frame)?;
+        frame.slots.borrow_mut()[offset] = value;
+        Ok(())
+    }
+
+    /// Returns the frame holding `name` and its offset in there.
+    fn slot<'a>(
+        &mut self,
+        name: &str,
+        env: &Rc<Env<'static>>,
+        frame: &Rc<Frame<'a>>,
+    ) -> Result<(Rc<Frame<'a>>, usize), InterpretError> {
+        let (jumps, offset) = env.lookup(name)?;
+        let mut frame = frame.clone();
+        for _ in 0..jumps {
+            frame = frame.parent.clone().unwrap();
+        }
+        Ok((frame, offset as usize))
+    }
+
+    fn closure<'a>(
+        &mut self,
+        params: &'a [Identifier],
+        body: &'a FunctionBody,
+        env: &Rc<Env<'static>>,
+        frame: &Rc<Frame<'a>>,
+    ) -> Value<'a> {
+        Value::Function(Rc::new(Closure {
+            params,
+            body,
+            env: env.clone(),
+            frame: frame.clone(),
+        }))
+    }
+
+    fn unary<'a>(
+        &mut self,
+        expr: &'a UnaryExpression,
+        env: &Rc<Env<'static>>,
+        frame: &Rc<Frame<'a>>,
+    ) -> Result<Value<'a>, InterpretError> {
+        let argument = self.expr(&expr.argument, env, frame)?;
+
+        match (expr.operator, &argument) {
+            (UnaryOperator::Not, Value::Boolean(value)) => Ok(Value::Boolean(!value)),
+            (UnaryOperator::Not, _) => Err(type_error(expr.loc, "", "boolean", &argument)),
+            (UnaryOperator::Minus, Value::Number(value)) => Ok(Value::Number(-value)),
+            (UnaryOperator::Minus, _) => Err(type_error(expr.loc, "", "number", &argument)),
+        }
+    }
+
+    fn binary<'a>(
+        &mut self,
+        expr: &'a BinaryExpression,
+        env: &Rc<Env<'static>>,
+        frame: &Rc<Frame<'a>>,
+    ) -> Result<Value<'a>, InterpretError> {
+        let left = self.expr(&expr.left, env, frame)?;
+        let right = self.expr(&expr.right, env, frame)?;
+        let loc = expr.loc;
+
+        match expr.operator {
+            BinaryOperator::Plus => match (&left, &right) {
+                (Value::Number(l), Value::Number(r)) => Ok(Value::Number(l + r)),
+                (Value::String(l), Value::String(r)) => {
+                    Ok(Value::String(format!("{}{}", l, r).into()))
+                }
+                _ => Err(number_or_string_error(loc, &left, &right)),
+            },
+            BinaryOperator::Minus
+            | BinaryOperator::Times
+            | BinaryOperator::Divide
+            | BinaryOperator::Modulo => {
+                let (l, r) = match (&left, &right) {
+                    (Value::Number(l), Value::Number(r)) => (*l, *r),
+                    (Value::Number(_), _) => return Err(type_error(loc, RIGHT, "number", &right)),
+                    _ => return Err(type_error(loc, LEFT, "number", &left)),
+                };
+                Ok(Value::Number(match expr.operator {
+                    BinaryOperator::Minus => l - r,
+                    BinaryOperator::Times => l * r,
+                    BinaryOperator::Divide => l / r,
+                    _ => l % r,
+                }))
+            }
+            BinaryOperator::Less
+            | BinaryOperator::Greater
+            | BinaryOperator::LessEqual
+            | BinaryOperator::GreaterEqual => {
+                // NaN is unordered, so every comparison with it is false
+                let ordering = match (&left, &right) {
+                    (Value::Number(l), Value::Number(r)) => l.partial_cmp(r),
+                    (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+                    _ => return Err(number_or_string_error(loc, &left, &right)),
+                };
+                Ok(Value::Boolean(match (expr.operator, ordering) {
+                    (_, None) => false,
+                    (BinaryOperator::Less, Some(ordering)) => ordering == Ordering::Less,
+                    (BinaryOperator::Greater, Some(ordering)) => ordering == Ordering::Greater,
+                    (BinaryOperator::LessEqual, Some(ordering)) => ordering != Ordering::Greater,
+                    (_, Some(ordering)) => ordering != Ordering::Less,
+                }))
+            }
+            BinaryOperator::StrictEqual => Ok(Value::Boolean(strict_equal(&left, &right))),
+            BinaryOperator::StrictNotEqual => Ok(Value::Boolean(!strict_equal(&left, &right))),
+        }
+    }
+
+    fn logical<'a>(
+        &mut self,
+        expr: &'a LogicalExpression,
+        env: &Rc<Env<'static>>,
+        frame: &Rc<Frame<'a>>,
+    ) -> Result<Value<'a>, InterpretError> {
+        let left = self.expr(&expr.left, env, frame)?;
+
+        match (expr.operator, &left) {
+            (LogicalOperator::And, Value::Boolean(true))
+            | (LogicalOperator::Or, Value::Boolean(false)) => self.expr(&expr.right, env, frame),
+            (_, Value::Boolean(_)) => Ok(left),
+            _ => Err(type_error(expr.loc, LEFT, "boolean", &left)),
+        }
+    }
+
+    fn condition<'a>(
+        &mut self,
+        test: &'a Expr,
+        loc: Option<SourceLocation>,
+        env: &Rc<Env<'static>>,
+        frame: &Rc<Frame<'a>>,
+    ) -> Result<bool, InterpretError> {
+        match self.expr(test, env, frame)? {
+            Value::Boolean(value) => Ok(value),
+            value => Err(type_error(loc, " as condition", "boolean", &value)),
+        }
+    }
+
+    /// Evaluates the arguments and then the callee of a call, like the compiled
+    /// code, returning the call to make. Calls to built-in functions by their
+    /// name are made right away, returning their value.
+    fn callee_and_args<'a>(
+        &mut self,
+        expr: &'a CallExpression,
+        env: &Rc<Env<'static>>,
+        frame: &Rc<Frame<'a>>,
+    ) -> Result<Completion<'a>, InterpretError> {
+        let args = expr
+            .arguments
+            .iter()
+            .map(|arg| self.expr(arg, env, frame))
+            .collect::<Result<Vec<_>, InterpretError>>()?;
+
+        if let Expr::Identifier(callee) = &*expr.callee {
+            if is_builtin(&callee.name) && env.lookup(&callee.name).is_err() {
+                return self
+                    .builtin(&callee.name, args, expr.loc)
+                    .map(Completion::Value);
+            }
+        }
+
+        let closure = match self.expr(&expr.callee, env, frame)? {
+            Value::Function(closure) => closure,
+            // checked like the arity of any closure, but called by `apply`
+            Value::Builtin(name) => {
+                if let Some((_, Some(arity))) = find_builtin(name) {
+                    if *arity != args.len() {
+                        return Err(runtime_error(
+                            expr.loc,
+                            format!("Expected {} arguments, but got {}.", arity, args.len()),
+                        ));
+                    }
+                }
+                return Ok(Completion::BuiltinCall(name, args));
+            }
+            value => {
+                return Err(runtime_error(
+                    expr.loc,
+                    format!("Calling non-function value {}.", value),
+                ))
+            }
+        };
+
+        if closure.params.len() != args.len() {
+            return Err(runtime_error(
+                expr.loc,
+                format!(
+                    "Expected {} arguments, but got {}.",
+                    closure.params.len(),
+                    args.len()
+                ),
+            ));
+        }
+
+        Ok(Completion::TailCall(closure, args))
+    }
+
+    /// Calls a built-in function, which the checker or the call of its value
+    /// has made sure gets the number of arguments it takes.
+    fn builtin<'a>(
+        &mut self,
+        name: &str,
+        args: Vec<Value<'a>>,
+        loc: Option<SourceLocation>,
+    ) -> Result<Value<'a>, InterpretError> {
+        let pair = |value: &Value<'a>| match value {
+            Value::Pair(pair) => Ok(pair.clone()),
+            _ => Err(type_error(loc, "", "pair", value)),
+        };
+
+        Ok(match name {
+            "display" => {
+                writeln!(self.out, "{}", args[0])?;
+                Value::Undefined
+            }
+            "pair" => Value::pair(args[0].clone(), args[1].clone()),
+            // a list is built from its last element
+            "list" => args
+                .into_iter()
+                .rev()
+                .fold(Value::Null, |tail, head| Value::pair(head, tail)),
+            "head" => pair(&args[0])?.head.borrow().clone(),
+            "tail" => pair(&args[0])?.tail.borrow().clone(),
+            "set_head" => {
+                *pair(&args[0])?.head.borrow_mut() = args[1].clone();
+                Value::Undefined
+            }
+            "set_tail" => {
+                *pair(&args[0])?.tail.borrow_mut() = args[1].clone();
+                Value::Undefined
+            }
+            "is_pair" => Value::Boolean(matches!(args[0], Value::Pair(_))),
+            "is_null" => Value::Boolean(matches!(args[0], Value::Null)),
+            "stringify" => Value::String(args[0].to_string().into()),
+            _ => unreachable!("{} is not a built-in function", name),
+        })
+    }
+
+    /// Makes the call, and then the tail calls it returns like the
+    /// `trampoline` runtime function.
+    fn apply<'a>(&mut self, call: Completion<'a>) -> Result<Value<'a>, InterpretError> {
+        let mut completion = call;
+
+        loop {
+            match completion {
+                Completion::Value(value) => break Ok(value),
+                Completion::TailCall(closure, args) => {
+                    completion = self.call(&closure, args)?;
+                }
+                // like the code of a built-in function's closure, without a location
+                Completion::BuiltinCall(name, args) => {
+                    completion = Completion::Value(self.builtin(name, args, None)?);
+                }
+            }
+        }
+    }
+
+    fn call<'a>(
+        &mut self,
+        closure: &Closure<'a>,
+        args: Vec<Value<'a>>,
+    ) -> Result<Completion<'a>, InterpretError> {
+        let mut env = Env::new(Some(closure.env.clone()));
+        closure
+            .params
+            .iter()
+            .for_each(|param| env.add_name(param.name.clone()));
+        let env = Rc::new(env);
+
+        let frame = Frame::new(0, Some(closure.frame.clone()));
+        frame.slots.borrow_mut().extend(args);
+        let frame = Rc::new(frame);
+
+        match closure.body {
+            FunctionBody::Expression(expr) => self.tail_expr(expr, &env, &frame),
+            FunctionBody::Block(block) => Ok(self
+                .block(block, &env, &frame)?
+                .unwrap_or(Completion::Value(Value::Undefined))),
+        }
+    }
+}
+
+/// Raises the type error of `+` and the comparison operators, where the left
+/// operand decides what the right operand is expected to be.
+fn number_or_string_error(
+    loc: Option<SourceLocation>,
+    left: &Value,
+    right: &Value,
+) -> InterpretError {
+    match left {
+        Value::Number(_) => type_error(loc, RIGHT, "number", right),
+        Value::String(_) => type_error(loc, RIGHT, "string", right),
+        _ => type_error(loc, LEFT, "string or number", left),
+    }
+}
+
+/// Compares values like the `strict_equal` runtime function.
+fn strict_equal<'a>(left: &Value<'a>, right: &Value<'a>) -> bool {
+    match (left, right) {
+        (Value::Undefined, Value::Undefined) => true,
+        (Value::Boolean(l), Value::Boolean(r)) => l == r,
+        (Value::Number(l), Value::Number(r)) => l == r,
+        (Value::String(l), Value::String(r)) => l == r,
+        (Value::Function(l), Value::Function(r)) => Rc::ptr_eq(l, r),
+        (Value::Builtin(l), Value::Builtin(r)) => l == r,
+        (Value::Null, Value::Null) => true,
+        (Value::Pair(l), Value::Pair(r)) => Rc::ptr_eq(l, r),
+        _ => false,
+    }
+}
@@ -0,0 +1,295 @@
+use crate::ast;
+use crate::error::CompileError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl From<Position> for ast::Position {
+    fn from(position: Position) -> Self {
+        ast::Position {
+            line: position.line,
+            column: position.column,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum TokenKind {
+    /// The value of a number literal, and its text.
+    Number(f64, String),
+    String(String),
+    Name(String),
+    Keyword(&'static str),
+    Punct(&'static str),
+    Eof,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct Token {
+    pub kind: TokenKind,
+    pub start: Position,
+    pub end: Position,
+}
+
+const KEYWORDS: &[&str] = &[
+    "const",
+    "else",
+    "false",
+    "function",
+    "if",
+    "null",
+    "return",
+    "true",
+    // reserved, but not part of Source §2
+    "break",
+    "case",
+    "catch",
+    "class",
+    "continue",
+    "debugger",
+    "default",
+    "delete",
+    "do",
+    "export",
+    "extends",
+    "finally",
+    "for",
+    "import",
+    "in",
+    "instanceof",
+    "let",
+    "new",
+    "switch",
+    "this",
+    "throw",
+    "try",
+    "typeof",
+    "var",
+    "void",
+    "while",
+    "with",
+    "yield",
+];
+
+// Longest first so that e.g. `===` is not lexed as `==` followed by `=`.
+const PUNCTUATORS: &[&str] = &[
+    "===", "!==", "=>", "<=", ">=", "&&", "||", "==", "!=", "(", ")", "{", "}", ",", ";", "+", "-",
+    "*", "/", "%", "!", "<", ">", "?", ":", "=",
+];
+
+pub(crate) fn tokenize(source: &str) -> Result<Vec<Token>, CompileError> {
+    let mut lexer = Lexer {
+        chars: source.chars().collect(),
+        index: 0,
+        line: 1,
+        column: 0,
+    };
+    let mut tokens = vec![];
+
+    loop {
+        lexer.skip_whitespace_and_comments()?;
+        let start = lexer.position();
+        let kind = match lexer.peek() {
+            None => TokenKind::Eof,
+            Some(c) if c.is_ascii_digit() => lexer.number()?,
+            Some('.') if lexer.peek_at(1).map_or(false, |c| c.is_ascii_digit()) => {
+                lexer.number()?
+            }
+            Some(c) if c == '"' || c == '\'' => lexer.string(c)?,
+            Some(c) if is_name_start(c) => lexer.name(),
+            Some(_) => lexer.punctuator()?,
+        };
+        let end = lexer.position();
+        let is_eof = kind == TokenKind::Eof;
+        tokens.push(Token { kind, start, end });
+        if is_eof {
+            break Ok(tokens);
+        }
+    }
+}
+
+fn is_name_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_' || c == '$'
+}
+
+fn is_name_part(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+struct Lexer {
+    chars: Vec<char>,
+    index: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Lexer {
+    fn position(&self) -> Position {
+        Position {
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.peek_at(0)
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.index + offset).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.index += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 0;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn error(&self, message: &str) -> CompileError {
+        CompileError::Syntax {
+            message: message.into(),
+            position: self.position().into(),
+        }
+    }
+
+    fn skip_whitespace_and_comments(&mut self) -> Result<(), CompileError> {
+        loop {
+            match (self.peek(), self.peek_at(1)) {
+                (Some(c), _) if c.is_whitespace() => {
+                    self.bump();
+                }
+                (Some('/'), Some('/')) => {
+                    while self.peek().map_or(false, |c| c != '\n') {
+                        self.bump();
+                    }
+                }
+                (Some('/'), Some('*')) => {
+                    self.bump();
+                    self.bump();
+                    loop {
+                        match (self.peek(), self.peek_at(1)) {
+                            (Some('*'), Some('/')) => {
+                                self.bump();
+                                self.bump();
+                                break;
+                            }
+                            (Some(_), _) => {
+                                self.bump();
+                            }
+                            (None, _) => return Err(self.error("Unterminated comment")),
+                        }
+                    }
+                }
+                _ => break Ok(()),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<TokenKind, CompileError> {
+        let mut text = String::new();
+
+        while let Some(c) = self.peek().filter(|c| c.is_ascii_digit()) {
+            text.push(c);
+            self.bump();
+        }
+        if self.peek() == Some('.') {
+            text.push('.');
+            self.bump();
+            while let Some(c) = self.peek().filter(|c| c.is_ascii_digit()) {
+                text.push(c);
+                self.bump();
+            }
+        }
+        if let Some(e) = self.peek().filter(|&c| c == 'e' || c == 'E') {
+            text.push(e);
+            self.bump();
+            if let Some(sign) = self.peek().filter(|&c| c == '+' || c == '-') {
+                text.push(sign);
+                self.bump();
+            }
+            if !self.peek().map_or(false, |c| c.is_ascii_digit()) {
+                return Err(self.error("Invalid number"));
+            }
+            while let Some(c) = self.peek().filter(|c| c.is_ascii_digit()) {
+                text.push(c);
+                self.bump();
+            }
+        }
+        if self.peek().map_or(false, is_name_start) {
+            return Err(self.error("Identifier directly after number"));
+        }
+
+        match text.parse() {
+            Ok(value) => Ok(TokenKind::Number(value, text)),
+            Err(_) => Err(self.error("Invalid number")),
+        }
+    }
+
+    fn string(&mut self, quote: char) -> Result<TokenKind, CompileError> {
+        let mut value = String::new();
+        self.bump();
+
+        loop {
+            match self.bump() {
+                Some(c) if c == quote => break Ok(TokenKind::String(value)),
+                Some('\\') => {
+                    let escaped = match self.bump() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('v') => '\u{b}',
+                        Some('0') => '\0',
+                        Some('\n') => continue,
+                        Some(c) => c,
+                        None => return Err(self.error("Unterminated string constant")),
+                    };
+                    value.push(escaped);
+                }
+                Some('\n') | None => return Err(self.error("Unterminated string constant")),
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    fn name(&mut self) -> TokenKind {
+        let mut name = String::new();
+        while let Some(c) = self.peek().filter(|&c| is_name_part(c)) {
+            name.push(c);
+            self.bump();
+        }
+
+        match KEYWORDS.iter().find(|&&k| k == name) {
+            Some(keyword) => TokenKind::Keyword(keyword),
+            None => TokenKind::Name(name),
+        }
+    }
+
+    fn punctuator(&mut self) -> Result<TokenKind, CompileError> {
+        let punct = PUNCTUATORS
+            .iter()
+            .find(|p| {
+                p.chars()
+                    .enumerate()
+                    .all(|(i, c)| self.peek_at(i) == Some(c))
+            })
+            .ok_or_else(|| {
+                self.error(&format!("Unexpected character {:?}", self.peek().unwrap()))
+            })?;
+        for _ in 0..punct.len() {
+            self.bump();
+        }
+
+        Ok(TokenKind::Punct(punct))
+    }
+}
@@ -2,42 +2,343 @@ use anyhow::{anyhow, Error};
 use inkwell::{
     builder::Builder,
     context::Context,
+    execution_engine::JitFunction,
     module::Module,
-    targets::{CodeModel, InitializationConfig, RelocMode, Target, TargetTriple},
-    values::BasicValue,
-    AddressSpace, OptimizationLevel,
+    passes::PassManager,
+    targets::{
+        CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine, TargetTriple,
+    },
+    values::{BasicValue, BasicValueEnum, FunctionValue, PointerValue},
+    AddressSpace, FloatPredicate, IntPredicate, OptimizationLevel,
 };
-use serde_json::Value;
 
-use std::rc::Rc;
+use std::{io::Write, panic, path::Path, rc::Rc, str::FromStr, thread};
 
+pub mod ast;
+mod checker;
+pub mod diagnostics;
 mod env;
+mod error;
 mod expr;
+mod gc;
 mod helper;
+mod interp;
+mod lexer;
+mod parser;
+mod prelude;
 mod stmt;
 
-use helper::{allocate_env, build_undefined};
+use ast::Program;
+use gc::GC_RAW;
+use helper::{
+    allocate_env, build_string_value, build_undefined, size_type, ARITY_ERROR,
+    CALL_NON_FUNCTION_ERROR, CONDITION_TYPE_ERROR, LEFT_TYPE_ERROR, NULL, POINTER_BITS, QNAN,
+    RIGHT_TYPE_ERROR, STACK_OVERFLOW_ERROR, STRING_OR_NUMBER, UNARY_TYPE_ERROR, UNDEFINED,
+};
 use stmt::compile_stmt;
 
-pub fn compile(es_str: &str) -> Result<String, Error> {
-    let es_node: Value = serde_json::from_str(es_str)?;
+pub use error::{CompileError, CompileErrors, InterpretError};
+
+/// The target to compile for, as passed to LLVM, and the optimization level
+/// from 0 to 3.
+#[derive(Debug, Clone)]
+pub struct CompileOptions {
+    pub target_triple: String,
+    pub cpu: String,
+    pub features: String,
+    pub opt_level: u8,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        CompileOptions {
+            target_triple: "wasm32-unknown-wasi".into(),
+            cpu: "".into(),
+            features: "".into(),
+            opt_level: 0,
+        }
+    }
+}
+
+impl CompileOptions {
+    /// Targets the machine sourcec runs on, e.g. to run programs in-process.
+    pub fn host() -> Self {
+        CompileOptions {
+            target_triple: TargetMachine::get_default_triple()
+                .as_str()
+                .to_string_lossy()
+                .into(),
+            cpu: TargetMachine::get_host_cpu_name().to_string(),
+            features: TargetMachine::get_host_cpu_features().to_string(),
+            opt_level: 0,
+        }
+    }
+}
+
+/// The kinds of artifacts that can be emitted for a compiled module.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Emit {
+    LlvmIr,
+    LlvmBc,
+    Asm,
+    Obj,
+}
+
+impl Emit {
+    /// The conventional file extension of the artifact.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Emit::LlvmIr => "ll",
+            Emit::LlvmBc => "bc",
+            Emit::Asm => "s",
+            Emit::Obj => "o",
+        }
+    }
+}
+
+impl FromStr for Emit {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "llvm-ir" => Ok(Emit::LlvmIr),
+            "llvm-bc" => Ok(Emit::LlvmBc),
+            "asm" => Ok(Emit::Asm),
+            "obj" => Ok(Emit::Obj),
+            _ => Err(anyhow!(
+                "Unknown emit kind {}, expected llvm-ir, llvm-bc, asm or obj",
+                s
+            )),
+        }
+    }
+}
+
+/// Compiles a program given in its ESTree JSON representation, e.g. as
+/// produced by `scripts/parse`.
+pub fn compile(es_str: &str) -> Result<String, CompileErrors> {
+    compile_with_options(es_str, &CompileOptions::default())
+}
 
-    // We only compile to wasm32-unknown-wasi for now because it relies on the
-    // pointer size being 32 bit, but on paper it should be able to target other
-    // triples as well.
-    Target::initialize_webassembly(&InitializationConfig::default());
-    let target_triple = TargetTriple::create("wasm32-unknown-wasi");
-    let target = Target::from_triple(&target_triple).unwrap();
+pub fn compile_with_options(
+    es_str: &str,
+    options: &CompileOptions,
+) -> Result<String, CompileErrors> {
+    let program = parse_estree(es_str)?;
+    compile_program(&program, options, print_module)
+}
+
+/// Compiles a program given as Source §2 text.
+pub fn compile_source(source: &str) -> Result<String, CompileErrors> {
+    compile_source_with_options(source, &CompileOptions::default())
+}
+
+pub fn compile_source_with_options(
+    source: &str,
+    options: &CompileOptions,
+) -> Result<String, CompileErrors> {
+    let program = parse_source(source)?;
+    compile_program(&program, options, print_module)
+}
+
+/// Compiles a program given in its ESTree JSON representation and writes each
+/// of the artifacts to its path.
+pub fn emit<P: AsRef<Path>>(
+    es_str: &str,
+    options: &CompileOptions,
+    artifacts: &[(Emit, P)],
+) -> Result<(), CompileErrors> {
+    let program = parse_estree(es_str)?;
+    compile_program(&program, options, |module, target_machine| {
+        write_artifacts(module, target_machine, artifacts)
+    })
+}
+
+/// Compiles a program given as Source §2 text and writes each of the artifacts
+/// to its path.
+pub fn emit_source<P: AsRef<Path>>(
+    source: &str,
+    options: &CompileOptions,
+    artifacts: &[(Emit, P)],
+) -> Result<(), CompileErrors> {
+    let program = parse_source(source)?;
+    compile_program(&program, options, |module, target_machine| {
+        write_artifacts(module, target_machine, artifacts)
+    })
+}
+
+/// Compiles a program given in its ESTree JSON representation for the host and
+/// runs it in-process, returning the exit code of its `main`. The runtime's
+/// calls to libc, e.g. `printf`, go to the host's libc, so a runtime error
+/// exits the current process.
+pub fn run(es_str: &str, opt_level: u8) -> Result<i32, CompileErrors> {
+    let program = parse_estree(es_str)?;
+    let options = CompileOptions {
+        opt_level,
+        ..CompileOptions::host()
+    };
+    compile_program(&program, &options, |module, _| {
+        run_module(module, opt_level)
+    })
+}
+
+/// Compiles a program given as Source §2 text for the host and runs it
+/// in-process, see `run`.
+pub fn run_source(source: &str, opt_level: u8) -> Result<i32, CompileErrors> {
+    let program = parse_source(source)?;
+    let options = CompileOptions {
+        opt_level,
+        ..CompileOptions::host()
+    };
+    compile_program(&program, &options, |module, _| {
+        run_module(module, opt_level)
+    })
+}
+
+/// Interprets a program given in its ESTree JSON representation, writing what
+/// it displays to `out`. This is a reference for the compiled code: a runtime
+/// error is returned with the message the compiled program prints before
+/// exiting with code 1.
+pub fn interpret(es_str: &str, out: &mut dyn Write) -> Result<(), InterpretError> {
+    let es_str = es_str.to_owned();
+    on_interpreter_stack(out, move |out| {
+        let program = parse_estree(&es_str)?;
+        let prelude = prelude::prelude();
+        checker::check(&program, &prelude).map_err(CompileErrors)?;
+        interp::interpret(&program, &prelude, out)
+    })
+}
+
+/// Interprets a program given as Source §2 text, see `interpret`.
+pub fn interpret_source(source: &str, out: &mut dyn Write) -> Result<(), InterpretError> {
+    let source = source.to_owned();
+    on_interpreter_stack(out, move |out| {
+        let program = parse_source(&source)?;
+        let prelude = prelude::prelude();
+        checker::check(&program, &prelude).map_err(CompileErrors)?;
+        interp::interpret(&program, &prelude, out)
+    })
+}
+
+/// The native stack of the interpreter, which recurses several times for each
+/// call, so that it runs out of calls at `MAX_CALL_DEPTH` rather than out of
+/// stack.
+const INTERPRETER_STACK_SIZE: usize = 256 << 20;
+
+/// Runs `interpret` on a thread with a stack of `INTERPRETER_STACK_SIZE`, and
+/// writes what it displayed to `out` once it's done.
+fn on_interpreter_stack(
+    out: &mut dyn Write,
+    interpret: impl FnOnce(&mut dyn Write) -> Result<(), InterpretError> + Send + 'static,
+) -> Result<(), InterpretError> {
+    let (displayed, result) = thread::Builder::new()
+        .stack_size(INTERPRETER_STACK_SIZE)
+        .spawn(move || {
+            let mut displayed = vec![];
+            let result = interpret(&mut displayed);
+            (displayed, result)
+        })?
+        .join()
+        .unwrap_or_else(|payload| panic::resume_unwind(payload));
+    out.write_all(&displayed)?;
+    result
+}
+
+fn parse_estree(es_str: &str) -> Result<Program, CompileErrors> {
+    let value = serde_json::from_str(es_str).map_err(CompileError::from)?;
+    ast::from_estree(value).map_err(CompileErrors)
+}
+
+fn parse_source(source: &str) -> Result<Program, CompileErrors> {
+    let value = parser::parse(source).map_err(CompileErrors)?;
+    ast::from_estree(value).map_err(CompileErrors)
+}
+
+fn run_module(module: &Module, opt_level: u8) -> Result<i32, CompileError> {
+    let execution_engine = module
+        .create_jit_execution_engine(optimization_level(opt_level)?)
+        .map_err(|s| CompileError::Jit(s.to_string()))?;
+
+    // SAFETY: main is built with this signature in `compile_program`.
+    let exit_code = unsafe {
+        let main: JitFunction<unsafe extern "C" fn() -> i32> = execution_engine
+            .get_function("main")
+            .map_err(|err| CompileError::Jit(err.to_string()))?;
+        main.call()
+    };
+
+    Ok(exit_code)
+}
+
+fn print_module(module: &Module, _: &TargetMachine) -> Result<String, CompileError> {
+    Ok(module.print_to_string().to_string())
+}
+
+fn write_artifacts<P: AsRef<Path>>(
+    module: &Module,
+    target_machine: &TargetMachine,
+    artifacts: &[(Emit, P)],
+) -> Result<(), CompileError> {
+    for (kind, path) in artifacts {
+        let path = path.as_ref();
+        match kind {
+            Emit::LlvmIr => module
+                .print_to_file(path)
+                .map_err(|s| CompileError::Emit(s.to_string()))?,
+            Emit::LlvmBc => {
+                if !module.write_bitcode_to_path(path) {
+                    return Err(CompileError::Emit(format!(
+                        "Could not write bitcode to {}",
+                        path.display()
+                    )));
+                }
+            }
+            Emit::Asm => target_machine
+                .write_to_file(module, FileType::Assembly, path)
+                .map_err(|s| CompileError::Emit(s.to_string()))?,
+            Emit::Obj => target_machine
+                .write_to_file(module, FileType::Object, path)
+                .map_err(|s| CompileError::Emit(s.to_string()))?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Compiles the program into a module for the target and hands both to
+/// `output`, which turns them into the result.
+///
+/// Errors about the program are collected by the checker, and the code
+/// generator can't fail on a program the checker accepts, so only the first
+/// error after it is returned; one there is a bug in the code generator, or
+/// about the target or output rather than the program.
+fn compile_program<T>(
+    program: &Program,
+    options: &CompileOptions,
+    output: impl FnOnce(&Module, &TargetMachine) -> Result<T, CompileError>,
+) -> Result<T, CompileErrors> {
+    let prelude = prelude::prelude();
+    checker::check(program, &prelude).map_err(CompileErrors)?;
+    let optimization_level = optimization_level(options.opt_level)?;
+
+    Target::initialize_all(&InitializationConfig::default());
+    let target_triple = TargetTriple::create(&options.target_triple);
+    let target =
+        Target::from_triple(&target_triple).map_err(|s| CompileError::Target(s.to_string()))?;
     let target_machine = target
         .create_target_machine(
             &target_triple,
-            "",
-            "",
-            OptimizationLevel::None,
+            &options.cpu,
+            &options.features,
+            optimization_level,
             RelocMode::Default,
             CodeModel::Default,
         )
-        .unwrap();
+        .ok_or_else(|| {
+            CompileError::Target(format!(
+                "Could not create a target machine for {}",
+                options.target_triple
+            ))
+        })?;
     let target_data_layout = target_machine.get_target_data().get_data_layout();
 
     let context = &Context::create();
@@ -56,26 +357,33 @@ pub fn compile(es_str: &str) -> Result<String, Error> {
         let entry = context.append_basic_block(main_function, "entry");
         builder.position_at_end(entry);
 
+        // the prelude's frame is the parent of the program's top-level frame,
+        // so that the program's declarations shadow the prelude's
+        let prelude_env = Rc::new(allocate_env(&prelude.body, None, context, module, builder)?);
+        for s in prelude.body.iter() {
+            compile_stmt(
+                s,
+                prelude_env.clone(),
+                context,
+                module,
+                builder,
+                &main_function,
+            )?;
+        }
+
         let env = Rc::new(allocate_env(
-            es_node.get("body").unwrap().as_array().unwrap(),
-            None,
+            &program.body,
+            Some(prelude_env),
             context,
             module,
             builder,
         )?);
 
-        let last = es_node
-            .get("body")
-            .unwrap()
-            .as_array()
-            .unwrap()
-            .iter()
-            .map(|s| {
-                compile_stmt(s, env.clone(), context, module, builder, &main_function).unwrap()
-            })
-            .last()
-            .unwrap();
-        let result = last.unwrap_or(build_undefined(context, module, builder)?);
+        let mut last = None;
+        for s in program.body.iter() {
+            last = compile_stmt(s, env.clone(), context, module, builder, &main_function)?;
+        }
+        let result = last.unwrap_or(build_undefined(context));
         let display_fn = module.get_function("display").unwrap();
         builder.build_call(display_fn, &[result.into()], "");
 
@@ -83,16 +391,78 @@ pub fn compile(es_str: &str) -> Result<String, Error> {
         builder.build_return(Some(&_0));
     }
 
-    module.verify().map_err(|s| anyhow!(s.to_string()))?;
+    module
+        .verify()
+        .map_err(|s| CompileError::LlvmVerify(s.to_string()))?;
 
-    Ok(module.print_to_string().to_string())
+    optimize(module, options.opt_level);
+
+    Ok(output(module, &target_machine)?)
+}
+
+fn optimization_level(opt_level: u8) -> Result<OptimizationLevel, CompileError> {
+    match opt_level {
+        0 => Ok(OptimizationLevel::None),
+        1 => Ok(OptimizationLevel::Less),
+        2 => Ok(OptimizationLevel::Default),
+        3 => Ok(OptimizationLevel::Aggressive),
+        level => Err(CompileError::UnknownOptLevel(level)),
+    }
+}
+
+/// Runs the pass pipeline of the optimization level on the module. Function
+/// passes run again after inlining, which mostly inlines the runtime functions
+/// into the compiled code.
+fn optimize(module: &Module, opt_level: u8) {
+    if opt_level == 0 {
+        return;
+    }
+
+    let function_passes = PassManager::create(module);
+    function_passes.add_promote_memory_to_register_pass();
+    function_passes.add_instruction_combining_pass();
+    function_passes.add_cfg_simplification_pass();
+    if opt_level >= 2 {
+        function_passes.add_reassociate_pass();
+        function_passes.add_gvn_pass();
+        function_passes.add_tail_call_elimination_pass();
+        function_passes.add_cfg_simplification_pass();
+    }
+    if opt_level >= 3 {
+        function_passes.add_aggressive_inst_combiner_pass();
+        function_passes.add_dead_store_elimination_pass();
+        function_passes.add_licm_pass();
+    }
+    function_passes.initialize();
+
+    let run_function_passes = |function_passes: &PassManager<FunctionValue>| {
+        for function in module.get_functions() {
+            function_passes.run_on(&function);
+        }
+    };
+    run_function_passes(&function_passes);
+
+    if opt_level >= 2 {
+        let module_passes = PassManager::create(());
+        module_passes.add_function_inlining_pass();
+        module_passes.add_global_dce_pass();
+        module_passes.run_on(module);
+
+        run_function_passes(&function_passes);
+    }
+
+    function_passes.finalize();
 }
 
+/// The size of the buffers `number_to_string` writes into, which fit any
+/// number.
+const NUMBER_BUFFER_SIZE: u64 = 64;
+
 fn setup<'ctx>(
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
-) -> Result<(), Error> {
+) -> Result<(), CompileError> {
     let i8_type = context.i8_type();
     let i8_ptr_type = i8_type.ptr_type(AddressSpace::Generic);
     let i32_type = context.i32_type();
@@ -100,85 +470,505 @@ fn setup<'ctx>(
     let void_type = context.void_type();
     let bool_type = context.bool_type();
     let f64_type = context.f64_type();
+    let size_type = size_type(context, module);
 
+    // strings, the payload being a pointer to the characters
     let source_obj_type = context.opaque_struct_type("source_obj");
     source_obj_type.set_body(&[i64_type.into(), i64_type.into()], false);
     let source_obj_ptr_type = source_obj_type.ptr_type(AddressSpace::Generic);
 
+    // frames and params are arrays of values, frames holding the parent frame
+    // in their first slot
+    let frame_type = i64_type.ptr_type(AddressSpace::Generic);
+
     let closure_type = context.opaque_struct_type("closure");
     closure_type.set_body(
         &[
             i64_type.into(),
-            source_obj_type
-                .ptr_type(AddressSpace::Generic)
-                .ptr_type(AddressSpace::Generic)
-                .into(),
-            source_obj_type
-                .ptr_type(AddressSpace::Generic)
-                .fn_type(
-                    &[
-                        source_obj_type
-                            .ptr_type(AddressSpace::Generic)
-                            .ptr_type(AddressSpace::Generic)
-                            .into(),
-                        source_obj_type
-                            .ptr_type(AddressSpace::Generic)
-                            .ptr_type(AddressSpace::Generic)
-                            .into(),
-                    ],
-                    false,
-                )
+            frame_type.into(),
+            i64_type
+                .fn_type(&[frame_type.into(), frame_type.into()], false)
                 .ptr_type(AddressSpace::Generic)
                 .into(),
+            // arity
+            i64_type.into(),
         ],
         false,
     );
+    let closure_ptr_type = closure_type.ptr_type(AddressSpace::Generic);
+
+    // A call in tail position is not made directly but returned to the
+    // caller's trampoline, so that iterative processes run in constant stack.
+    let tail_call_type = context.opaque_struct_type("tail_call");
+    tail_call_type.set_body(
+        &[i64_type.into(), closure_ptr_type.into(), frame_type.into()],
+        false,
+    );
+    let tail_call_ptr_type = tail_call_type.ptr_type(AddressSpace::Generic);
+
+    // pairs of a head and a tail value, lists being pairs ending in null
+    let pair_type = context.opaque_struct_type("pair");
+    pair_type.set_body(&[i64_type.into(), i64_type.into(), i64_type.into()], false);
+    let pair_ptr_type = pair_type.ptr_type(AddressSpace::Generic);
 
     let printf_type = i32_type.fn_type(&[i8_ptr_type.into()], true);
     module.add_function("printf", printf_type, None);
 
-    let malloc_type = i8_ptr_type.fn_type(&[i32_type.into()], false);
+    let malloc_type = i8_ptr_type.fn_type(&[size_type.into()], false);
     module.add_function("malloc", malloc_type, None);
 
     let exit_type = void_type.fn_type(&[i32_type.into()], false);
     module.add_function("exit", exit_type, None);
 
-    // display fn
+    let strlen_type = size_type.fn_type(&[i8_ptr_type.into()], false);
+    module.add_function("strlen", strlen_type, None);
+
+    let strcmp_type = i32_type.fn_type(&[i8_ptr_type.into(), i8_ptr_type.into()], false);
+    module.add_function("strcmp", strcmp_type, None);
+
+    let memcpy_type = i8_ptr_type.fn_type(
+        &[i8_ptr_type.into(), i8_ptr_type.into(), size_type.into()],
+        false,
+    );
+    module.add_function("memcpy", memcpy_type, None);
+
+    // kind, a, b, line, column; declared ahead of the GC, whose `gc_push_root`
+    // raises an error when the shadow stack is full, and defined below
+    let error_type = void_type.fn_type(
+        &[
+            i32_type.into(),
+            i64_type.into(),
+            i64_type.into(),
+            i32_type.into(),
+            i32_type.into(),
+        ],
+        false,
+    );
+    module.add_function("error", error_type, None);
+
+    // the number of calls being made other than tail calls, which is at most
+    // MAX_CALL_DEPTH
+    let call_depth = module.add_global(i32_type, None, "call_depth");
+    call_depth.set_initializer(&i32_type.const_zero());
+
+    gc::setup(context, module, builder)?;
+
+    let snprintf_type = i32_type.fn_type(
+        &[i8_ptr_type.into(), size_type.into(), i8_ptr_type.into()],
+        true,
+    );
+    module.add_function("snprintf", snprintf_type, None);
+
+    let strtod_type = f64_type.fn_type(
+        &[
+            i8_ptr_type.into(),
+            i8_ptr_type.ptr_type(AddressSpace::Generic).into(),
+        ],
+        false,
+    );
+    module.add_function("strtod", strtod_type, None);
+
+    let strchr_type = i8_ptr_type.fn_type(&[i8_ptr_type.into(), i32_type.into()], false);
+    module.add_function("strchr", strchr_type, None);
+
+    let atoi_type = i32_type.fn_type(&[i8_ptr_type.into()], false);
+    module.add_function("atoi", atoi_type, None);
+
+    // number to string fn, following ECMAScript's Number::toString. It writes
+    // into the caller's buffer, so that nothing is allocated for the string,
+    // and returns either the buffer or a constant string.
     {
-        let display_fn_type = void_type.fn_type(&[source_obj_ptr_type.into()], false);
-        let display_fn = module.add_function("display", display_fn_type, None);
+        let number_to_string_fn_type =
+            i8_ptr_type.fn_type(&[f64_type.into(), i8_ptr_type.into()], false);
+        let number_to_string_fn =
+            module.add_function("number_to_string", number_to_string_fn_type, None);
 
-        let printf_fn = module.get_function("printf").unwrap();
+        let snprintf_fn = module.get_function("snprintf").unwrap();
+        let strtod_fn = module.get_function("strtod").unwrap();
+        let strchr_fn = module.get_function("strchr").unwrap();
+        let atoi_fn = module.get_function("atoi").unwrap();
+
+        let entry = context.append_basic_block(number_to_string_fn, "entry");
+        let nan_block = context.append_basic_block(number_to_string_fn, "nan");
+        let check_zero_block = context.append_basic_block(number_to_string_fn, "check_zero");
+        let zero_block = context.append_basic_block(number_to_string_fn, "zero");
+        let check_inf_block = context.append_basic_block(number_to_string_fn, "check_inf");
+        let inf_block = context.append_basic_block(number_to_string_fn, "inf");
+        let check_neg_inf_block = context.append_basic_block(number_to_string_fn, "check_neg_inf");
+        let neg_inf_block = context.append_basic_block(number_to_string_fn, "neg_inf");
+        let finite_block = context.append_basic_block(number_to_string_fn, "finite");
+        let digits_block = context.append_basic_block(number_to_string_fn, "digits");
+        let format_block = context.append_basic_block(number_to_string_fn, "format");
+        let check_fraction_block =
+            context.append_basic_block(number_to_string_fn, "check_fraction");
+        let check_small_block = context.append_basic_block(number_to_string_fn, "check_small");
+        let integer_block = context.append_basic_block(number_to_string_fn, "integer");
+        let fraction_block = context.append_basic_block(number_to_string_fn, "fraction");
+        let small_block = context.append_basic_block(number_to_string_fn, "small");
+        let exponent_block = context.append_basic_block(number_to_string_fn, "exponent");
+
+        let _0 = i32_type.const_int(0, false);
+        let _1 = i32_type.const_int(1, false);
+        let _2 = i32_type.const_int(2, false);
+        let _17 = i32_type.const_int(17, false);
+        let _21 = i32_type.const_int(21, false);
+        let buffer_size = size_type.const_int(NUMBER_BUFFER_SIZE, false);
 
-        let entry = context.append_basic_block(display_fn, "entry");
         builder.position_at_end(entry);
-        let undefined_block = context.append_basic_block(display_fn, "undefined");
-        let boolean_block = context.append_basic_block(display_fn, "boolean");
-        let true_block = context.append_basic_block(display_fn, "true");
-        let false_block = context.append_basic_block(display_fn, "false");
-        let number_block = context.append_basic_block(display_fn, "number");
-        let function_block = context.append_basic_block(display_fn, "function");
-        let end_block = context.append_basic_block(display_fn, "end");
+        let x = number_to_string_fn
+            .get_first_param()
+            .unwrap()
+            .into_float_value();
+        let out = number_to_string_fn
+            .get_nth_param(1)
+            .unwrap()
+            .into_pointer_value();
+        let digits = builder.build_array_alloca(i8_type, buffer_size, "digits");
+        let is_nan = builder.build_float_compare(FloatPredicate::UNO, x, x, "");
+        builder.build_conditional_branch(is_nan, nan_block, check_zero_block);
 
-        let _0 = context.i32_type().const_int(0, false);
-        let _1 = context.i32_type().const_int(1, false);
+        // -0 is displayed as 0 as well
+        builder.position_at_end(check_zero_block);
+        let is_zero =
+            builder.build_float_compare(FloatPredicate::OEQ, x, f64_type.const_zero(), "");
+        builder.build_conditional_branch(is_zero, zero_block, check_inf_block);
+
+        builder.position_at_end(check_inf_block);
+        let is_inf = builder.build_float_compare(
+            FloatPredicate::OEQ,
+            x,
+            f64_type.const_float(f64::INFINITY),
+            "",
+        );
+        builder.build_conditional_branch(is_inf, inf_block, check_neg_inf_block);
+
+        builder.position_at_end(check_neg_inf_block);
+        let is_neg_inf = builder.build_float_compare(
+            FloatPredicate::OEQ,
+            x,
+            f64_type.const_float(f64::NEG_INFINITY),
+            "",
+        );
+        builder.build_conditional_branch(is_neg_inf, neg_inf_block, finite_block);
+
+        for (block, name) in [
+            (nan_block, "NaN"),
+            (zero_block, "0"),
+            (inf_block, "Infinity"),
+            (neg_inf_block, "-Infinity"),
+        ]
+        .iter()
+        {
+            builder.position_at_end(*block);
+            let number_str = builder.build_global_string_ptr(name, "number_str");
+            builder.build_return(Some(&number_str.as_pointer_value()));
+        }
+
+        builder.position_at_end(finite_block);
+        let is_negative =
+            builder.build_float_compare(FloatPredicate::OLT, x, f64_type.const_zero(), "");
+        let negated = builder.build_float_neg(x, "");
+        let abs = builder.build_select(is_negative, negated, x, "");
+        let minus_str = builder.build_global_string_ptr("-", "minus_str");
+        let empty_str = builder.build_global_string_ptr("", "empty_str");
+        let sign = builder.build_select(
+            is_negative,
+            minus_str.as_pointer_value(),
+            empty_str.as_pointer_value(),
+            "",
+        );
+        builder.build_unconditional_branch(digits_block);
 
-        let obj_type_ptr = unsafe {
-            builder.build_in_bounds_gep(
-                display_fn.get_first_param().unwrap().into_pointer_value(),
-                &[_0, _0],
+        // find the least number of significant digits k that round-trips,
+        // leaving d.ddde[+-]n in the digits buffer
+        builder.position_at_end(digits_block);
+        let k = builder.build_phi(i32_type, "k");
+        let k_value = k.as_basic_value().into_int_value();
+        let precision = builder.build_int_sub(k_value, _1, "");
+        let digits_fmt_str = builder.build_global_string_ptr("%.*e", "digits_fmt_str");
+        builder.build_call(
+            snprintf_fn,
+            &[
+                digits.into(),
+                buffer_size.into(),
+                digits_fmt_str.as_basic_value_enum(),
+                precision.into(),
+                abs,
+            ],
+            "",
+        );
+        let parsed = builder
+            .build_call(
+                strtod_fn,
+                &[
+                    digits.into(),
+                    i8_ptr_type
+                        .ptr_type(AddressSpace::Generic)
+                        .const_null()
+                        .into(),
+                ],
                 "",
             )
-        };
-        let obj_type = builder.build_load(obj_type_ptr, "").into_int_value();
-        let obj_value_ptr = unsafe {
-            builder.build_in_bounds_gep(
-                display_fn.get_first_param().unwrap().into_pointer_value(),
-                &[_0, _1],
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_float_value();
+        let round_trips =
+            builder.build_float_compare(FloatPredicate::OEQ, parsed, abs.into_float_value(), "");
+        let is_max_precision = builder.build_int_compare(IntPredicate::EQ, k_value, _17, "");
+        let done = builder.build_or(round_trips, is_max_precision, "");
+        let next_k = builder.build_int_add(k_value, _1, "");
+        k.add_incoming(&[(&_1, finite_block), (&next_k, digits_block)]);
+        builder.build_conditional_branch(done, format_block, digits_block);
+
+        // the decimal point comes after the first n digits
+        builder.position_at_end(format_block);
+        let e = builder
+            .build_call(
+                strchr_fn,
+                &[digits.into(), i32_type.const_int(b'e' as u64, false).into()],
                 "",
             )
-        };
-        let obj_value = builder.build_load(obj_value_ptr, "").into_int_value();
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value();
+        let exponent_ptr = unsafe { builder.build_in_bounds_gep(e, &[_1], "") };
+        let exponent = builder
+            .build_call(atoi_fn, &[exponent_ptr.into()], "")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let n = builder.build_int_add(exponent, _1, "");
+        let first_digit = builder.build_load(digits, "").into_int_value();
+        let first_digit = builder.build_int_s_extend(first_digit, i32_type, "");
+        let rest = unsafe { builder.build_in_bounds_gep(digits, &[_2], "") };
+        let rest_len = builder.build_int_sub(k_value, _1, "");
+        let zeros_str = builder.build_global_string_ptr("000000000000000000000", "zeros_str");
+        let n_le_21 = builder.build_int_compare(IntPredicate::SLE, n, _21, "");
+        let n_ge_k = builder.build_int_compare(IntPredicate::SGE, n, k_value, "");
+        let is_integer = builder.build_and(n_ge_k, n_le_21, "");
+        builder.build_conditional_branch(is_integer, integer_block, check_fraction_block);
+
+        builder.position_at_end(check_fraction_block);
+        let n_gt_0 = builder.build_int_compare(IntPredicate::SGT, n, _0, "");
+        let is_fraction = builder.build_and(n_gt_0, n_le_21, "");
+        builder.build_conditional_branch(is_fraction, fraction_block, check_small_block);
+
+        builder.position_at_end(check_small_block);
+        let n_gt_minus_6 = builder.build_int_compare(
+            IntPredicate::SGT,
+            n,
+            i32_type.const_int(-6i64 as u64, true),
+            "",
+        );
+        let n_le_0 = builder.build_not(n_gt_0, "");
+        let is_small = builder.build_and(n_gt_minus_6, n_le_0, "");
+        builder.build_conditional_branch(is_small, small_block, exponent_block);
+
+        // ddd000
+        builder.position_at_end(integer_block);
+        let zeros_len = builder.build_int_sub(n, k_value, "");
+        let integer_fmt_str = builder.build_global_string_ptr("%s%c%.*s%.*s", "integer_fmt_str");
+        builder.build_call(
+            snprintf_fn,
+            &[
+                out.into(),
+                buffer_size.into(),
+                integer_fmt_str.as_basic_value_enum(),
+                sign,
+                first_digit.into(),
+                rest_len.into(),
+                rest.into(),
+                zeros_len.into(),
+                zeros_str.as_basic_value_enum(),
+            ],
+            "",
+        );
+        builder.build_return(Some(&out));
+
+        // ddd.ddd
+        builder.position_at_end(fraction_block);
+        let integer_rest_len = builder.build_int_sub(n, _1, "");
+        let fraction = unsafe { builder.build_in_bounds_gep(rest, &[integer_rest_len], "") };
+        let fraction_len = builder.build_int_sub(k_value, n, "");
+        let fraction_fmt_str = builder.build_global_string_ptr("%s%c%.*s.%.*s", "fraction_fmt_str");
+        builder.build_call(
+            snprintf_fn,
+            &[
+                out.into(),
+                buffer_size.into(),
+                fraction_fmt_str.as_basic_value_enum(),
+                sign,
+                first_digit.into(),
+                integer_rest_len.into(),
+                rest.into(),
+                fraction_len.into(),
+                fraction.into(),
+            ],
+            "",
+        );
+        builder.build_return(Some(&out));
+
+        // 0.000ddd
+        builder.position_at_end(small_block);
+        let zeros_len = builder.build_int_neg(n, "");
+        let small_fmt_str = builder.build_global_string_ptr("%s0.%.*s%c%.*s", "small_fmt_str");
+        builder.build_call(
+            snprintf_fn,
+            &[
+                out.into(),
+                buffer_size.into(),
+                small_fmt_str.as_basic_value_enum(),
+                sign,
+                zeros_len.into(),
+                zeros_str.as_basic_value_enum(),
+                first_digit.into(),
+                rest_len.into(),
+                rest.into(),
+            ],
+            "",
+        );
+        builder.build_return(Some(&out));
+
+        // d.ddde+n
+        builder.position_at_end(exponent_block);
+        let is_single_digit = builder.build_int_compare(IntPredicate::EQ, k_value, _1, "");
+        let dot_str = builder.build_global_string_ptr(".", "dot_str");
+        let dot = builder.build_select(
+            is_single_digit,
+            empty_str.as_pointer_value(),
+            dot_str.as_pointer_value(),
+            "",
+        );
+        let exponent = builder.build_int_sub(n, _1, "");
+        let is_exponent_negative = builder.build_int_compare(IntPredicate::SLT, exponent, _0, "");
+        let exponent_sign = builder.build_select(
+            is_exponent_negative,
+            i32_type.const_int(b'-' as u64, false),
+            i32_type.const_int(b'+' as u64, false),
+            "",
+        );
+        let negated_exponent = builder.build_int_neg(exponent, "");
+        let abs_exponent =
+            builder.build_select(is_exponent_negative, negated_exponent, exponent, "");
+        let exponent_fmt_str =
+            builder.build_global_string_ptr("%s%c%s%.*se%c%d", "exponent_fmt_str");
+        builder.build_call(
+            snprintf_fn,
+            &[
+                out.into(),
+                buffer_size.into(),
+                exponent_fmt_str.as_basic_value_enum(),
+                sign,
+                first_digit.into(),
+                dot,
+                rest_len.into(),
+                rest.into(),
+                exponent_sign,
+                abs_exponent,
+            ],
+            "",
+        );
+        builder.build_return(Some(&out));
+    }
+
+    // value type fn, returning the type tag of a value
+    {
+        let value_type_fn_type = i64_type.fn_type(&[i64_type.into()], false);
+        let value_type_fn = module.add_function("value_type", value_type_fn_type, None);
+
+        let entry = context.append_basic_block(value_type_fn, "entry");
+        let number_block = context.append_basic_block(value_type_fn, "number");
+        let not_number_block = context.append_basic_block(value_type_fn, "not_number");
+        let pointer_block = context.append_basic_block(value_type_fn, "pointer");
+        let immediate_block = context.append_basic_block(value_type_fn, "immediate");
+
+        builder.position_at_end(entry);
+        let value = value_type_fn.get_first_param().unwrap().into_int_value();
+        let qnan = i64_type.const_int(QNAN, false);
+        let masked = builder.build_and(value, qnan, "");
+        let is_number = builder.build_int_compare(IntPredicate::NE, masked, qnan, "");
+        builder.build_conditional_branch(is_number, number_block, not_number_block);
+
+        builder.position_at_end(number_block);
+        builder.build_return(Some(&i64_type.const_int(2, false)));
+
+        builder.position_at_end(not_number_block);
+        let pointer_bits = i64_type.const_int(POINTER_BITS, false);
+        let masked = builder.build_and(value, pointer_bits, "");
+        let is_pointer = builder.build_int_compare(IntPredicate::EQ, masked, pointer_bits, "");
+        builder.build_conditional_branch(is_pointer, pointer_block, immediate_block);
+
+        builder.position_at_end(pointer_block);
+        let address = builder.build_and(value, i64_type.const_int(!POINTER_BITS, false), "");
+        let tag_ptr = builder.build_int_to_ptr(address, frame_type, "");
+        let tag = builder.build_load(tag_ptr, "");
+        builder.build_return(Some(&tag));
+
+        // everything else is undefined, null or a boolean
+        builder.position_at_end(immediate_block);
+        let is_undefined = builder.build_int_compare(
+            IntPredicate::EQ,
+            value,
+            i64_type.const_int(UNDEFINED, false),
+            "",
+        );
+        let is_null =
+            builder.build_int_compare(IntPredicate::EQ, value, i64_type.const_int(NULL, false), "");
+        let type_ = builder.build_select(
+            is_undefined,
+            i64_type.const_int(0, false),
+            i64_type.const_int(1, false),
+            "",
+        );
+        let type_ = builder.build_select(
+            is_null,
+            i64_type.const_int(7, false),
+            type_.into_int_value(),
+            "",
+        );
+        builder.build_return(Some(&type_));
+    }
+
+    // print value fn, i.e. display without the trailing newline
+    {
+        let print_value_fn_type = void_type.fn_type(&[i64_type.into()], false);
+        let print_value_fn = module.add_function("print_value", print_value_fn_type, None);
+
+        let printf_fn = module.get_function("printf").unwrap();
+        let number_to_string_fn = module.get_function("number_to_string").unwrap();
+        let value_type_fn = module.get_function("value_type").unwrap();
+
+        let entry = context.append_basic_block(print_value_fn, "entry");
+        builder.position_at_end(entry);
+        let undefined_block = context.append_basic_block(print_value_fn, "undefined");
+        let boolean_block = context.append_basic_block(print_value_fn, "boolean");
+        let true_block = context.append_basic_block(print_value_fn, "true");
+        let false_block = context.append_basic_block(print_value_fn, "false");
+        let number_block = context.append_basic_block(print_value_fn, "number");
+        let function_block = context.append_basic_block(print_value_fn, "function");
+        let string_block = context.append_basic_block(print_value_fn, "string");
+        let pair_block = context.append_basic_block(print_value_fn, "pair");
+        let pair_loop_block = context.append_basic_block(print_value_fn, "pair.loop");
+        let pair_end_block = context.append_basic_block(print_value_fn, "pair.end");
+        let close_loop_block = context.append_basic_block(print_value_fn, "close.loop");
+        let close_body_block = context.append_basic_block(print_value_fn, "close.body");
+        let null_block = context.append_basic_block(print_value_fn, "null");
+        let end_block = context.append_basic_block(print_value_fn, "end");
+
+        let _0 = context.i32_type().const_int(0, false);
+        let _1 = context.i32_type().const_int(1, false);
+        let _2 = context.i32_type().const_int(2, false);
+
+        let value = print_value_fn.get_first_param().unwrap().into_int_value();
+        let obj_type = builder
+            .build_call(value_type_fn, &[value.into()], "")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
         builder.build_switch(
             obj_type,
             undefined_block,
@@ -186,6 +976,9 @@ fn setup<'ctx>(
                 (i64_type.const_int(1, false), boolean_block),
                 (i64_type.const_int(2, false), number_block),
                 (i64_type.const_int(3, false), function_block),
+                (i64_type.const_int(4, false), string_block),
+                (i64_type.const_int(6, false), pair_block),
+                (i64_type.const_int(7, false), null_block),
             ],
         );
 
@@ -193,7 +986,7 @@ fn setup<'ctx>(
         {
             builder.position_at_end(undefined_block);
             let undefined_fmt_str =
-                builder.build_global_string_ptr("undefined\n", "undefined_fmt_str");
+                builder.build_global_string_ptr("undefined", "undefined_fmt_str");
             builder.build_call(printf_fn, &[undefined_fmt_str.as_basic_value_enum()], "");
             builder.build_unconditional_branch(end_block);
         }
@@ -201,16 +994,16 @@ fn setup<'ctx>(
         // boolean
         {
             builder.position_at_end(boolean_block);
-            let bool_value = builder.build_int_truncate(obj_value, bool_type, "");
+            let bool_value = builder.build_int_truncate(value, bool_type, "");
             builder.build_conditional_branch(bool_value, true_block, false_block);
 
             builder.position_at_end(true_block);
-            let true_fmt_str = builder.build_global_string_ptr("true\n", "true_fmt_str");
+            let true_fmt_str = builder.build_global_string_ptr("true", "true_fmt_str");
             builder.build_call(printf_fn, &[true_fmt_str.as_basic_value_enum()], "");
             builder.build_unconditional_branch(end_block);
 
             builder.position_at_end(false_block);
-            let false_fmt_str = builder.build_global_string_ptr("false\n", "false_fmt_str");
+            let false_fmt_str = builder.build_global_string_ptr("false", "false_fmt_str");
             builder.build_call(printf_fn, &[false_fmt_str.as_basic_value_enum()], "");
             builder.build_unconditional_branch(end_block);
         }
@@ -218,11 +1011,18 @@ fn setup<'ctx>(
         // number
         {
             builder.position_at_end(number_block);
-            let number_value = builder.build_bitcast(obj_value, f64_type, "");
-            let number_fmt_str = builder.build_global_string_ptr("%lf\n", "number_fmt_str");
+            let number_value = builder.build_bitcast(value, f64_type, "");
+            let buffer_size = size_type.const_int(NUMBER_BUFFER_SIZE, false);
+            let buffer = builder.build_array_alloca(i8_type, buffer_size, "number_buffer");
+            let number_str = builder
+                .build_call(number_to_string_fn, &[number_value, buffer.into()], "")
+                .try_as_basic_value()
+                .left()
+                .unwrap();
+            let number_fmt_str = builder.build_global_string_ptr("%s", "number_fmt_str");
             builder.build_call(
                 printf_fn,
-                &[number_fmt_str.as_basic_value_enum(), number_value],
+                &[number_fmt_str.as_basic_value_enum(), number_str],
                 "",
             );
             builder.build_unconditional_branch(end_block);
@@ -231,30 +1031,649 @@ fn setup<'ctx>(
         // function
         {
             builder.position_at_end(function_block);
-            let function_fmt_str =
-                builder.build_global_string_ptr("Function\n", "function_fmt_str");
+            let function_fmt_str = builder.build_global_string_ptr("Function", "function_fmt_str");
             builder.build_call(printf_fn, &[function_fmt_str.as_basic_value_enum()], "");
             builder.build_unconditional_branch(end_block);
         }
 
+        // string
+        {
+            builder.position_at_end(string_block);
+            let address = builder.build_and(value, i64_type.const_int(!POINTER_BITS, false), "");
+            let obj = builder.build_int_to_ptr(address, source_obj_ptr_type, "");
+            let obj_value_ptr = unsafe { builder.build_in_bounds_gep(obj, &[_0, _1], "") };
+            let obj_value = builder.build_load(obj_value_ptr, "").into_int_value();
+            let string_value = builder.build_int_to_ptr(obj_value, i8_ptr_type, "");
+            let string_fmt_str = builder.build_global_string_ptr("\"%s\"", "string_fmt_str");
+            builder.build_call(
+                printf_fn,
+                &[string_fmt_str.as_basic_value_enum(), string_value.into()],
+                "",
+            );
+            builder.build_unconditional_branch(end_block);
+        }
+
+        // pair, in box notation, e.g. [1, [2, null]]. The tails of lists are
+        // printed in a loop rather than recursively, closing their brackets at
+        // the end, so that long lists don't overflow the stack.
+        {
+            builder.position_at_end(pair_block);
+            builder.build_unconditional_branch(pair_loop_block);
+
+            builder.position_at_end(pair_loop_block);
+            let pair = builder.build_phi(i64_type, "pair");
+            let pair_value = pair.as_basic_value().into_int_value();
+            let depth = builder.build_phi(i64_type, "depth");
+            let depth_value = depth.as_basic_value().into_int_value();
+            let obj = builder.build_and(pair_value, i64_type.const_int(!POINTER_BITS, false), "");
+            let obj = builder.build_int_to_ptr(obj, pair_ptr_type, "");
+            let head_ptr = unsafe { builder.build_in_bounds_gep(obj, &[_0, _1], "") };
+            let head = builder.build_load(head_ptr, "");
+            let tail_ptr = unsafe { builder.build_in_bounds_gep(obj, &[_0, _2], "") };
+            let tail = builder.build_load(tail_ptr, "").into_int_value();
+            let open_str = builder.build_global_string_ptr("[", "open_str");
+            builder.build_call(printf_fn, &[open_str.as_basic_value_enum()], "");
+            builder.build_call(print_value_fn, &[head], "");
+            let separator_str = builder.build_global_string_ptr(", ", "separator_str");
+            builder.build_call(printf_fn, &[separator_str.as_basic_value_enum()], "");
+            let tail_type = builder
+                .build_call(value_type_fn, &[tail.into()], "")
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_int_value();
+            let is_pair = builder.build_int_compare(
+                IntPredicate::EQ,
+                tail_type,
+                i64_type.const_int(6, false),
+                "",
+            );
+            let next_depth = builder.build_int_add(depth_value, i64_type.const_int(1, false), "");
+            builder.build_conditional_branch(is_pair, pair_loop_block, pair_end_block);
+            pair.add_incoming(&[(&value, pair_block), (&tail, pair_loop_block)]);
+            depth.add_incoming(&[
+                (&i64_type.const_int(1, false), pair_block),
+                (&next_depth, pair_loop_block),
+            ]);
+
+            builder.position_at_end(pair_end_block);
+            builder.build_call(print_value_fn, &[tail.into()], "");
+            builder.build_unconditional_branch(close_loop_block);
+
+            builder.position_at_end(close_loop_block);
+            let remaining = builder.build_phi(i64_type, "remaining");
+            let remaining_value = remaining.as_basic_value().into_int_value();
+            let is_done = builder.build_int_compare(
+                IntPredicate::EQ,
+                remaining_value,
+                i64_type.const_zero(),
+                "",
+            );
+            builder.build_conditional_branch(is_done, end_block, close_body_block);
+
+            builder.position_at_end(close_body_block);
+            let close_str = builder.build_global_string_ptr("]", "close_str");
+            builder.build_call(printf_fn, &[close_str.as_basic_value_enum()], "");
+            let next_remaining =
+                builder.build_int_sub(remaining_value, i64_type.const_int(1, false), "");
+            builder.build_unconditional_branch(close_loop_block);
+            remaining.add_incoming(&[
+                (&depth_value, pair_end_block),
+                (&next_remaining, close_body_block),
+            ]);
+        }
+
+        // null
+        {
+            builder.position_at_end(null_block);
+            let null_fmt_str = builder.build_global_string_ptr("null", "null_fmt_str");
+            builder.build_call(printf_fn, &[null_fmt_str.as_basic_value_enum()], "");
+            builder.build_unconditional_branch(end_block);
+        }
+
         builder.position_at_end(end_block);
         builder.build_return(None);
     }
 
-    // error fn
+    // display fn
     {
-        let error_fn_type = void_type.fn_type(&[], false);
-        let error_fn = module.add_function("error", error_fn_type, None);
+        let display_fn_type = void_type.fn_type(&[i64_type.into()], false);
+        let display_fn = module.add_function("display", display_fn_type, None);
+
+        let print_value_fn = module.get_function("print_value").unwrap();
+        let printf_fn = module.get_function("printf").unwrap();
+
+        let entry = context.append_basic_block(display_fn, "entry");
+        builder.position_at_end(entry);
+
+        let value = display_fn.get_first_param().unwrap();
+        builder.build_call(print_value_fn, &[value], "");
+        let newline_str = builder.build_global_string_ptr("\n", "newline_str");
+        builder.build_call(printf_fn, &[newline_str.as_basic_value_enum()], "");
+        builder.build_return(None);
+    }
+
+    // type name fn
+    {
+        let type_name_fn_type = i8_ptr_type.fn_type(&[i64_type.into()], false);
+        let type_name_fn = module.add_function("type_name", type_name_fn_type, None);
+
+        let entry = context.append_basic_block(type_name_fn, "entry");
+        let names = [
+            (0, "undefined"),
+            (1, "boolean"),
+            (2, "number"),
+            (3, "function"),
+            (4, "string"),
+            (6, "pair"),
+            (7, "null"),
+            (STRING_OR_NUMBER, "string or number"),
+        ];
+        let blocks: Vec<_> = names
+            .iter()
+            .map(|(_, name)| context.append_basic_block(type_name_fn, name))
+            .collect();
+
+        builder.position_at_end(entry);
+        let type_ = type_name_fn.get_first_param().unwrap().into_int_value();
+        let cases: Vec<_> = names
+            .iter()
+            .zip(blocks.iter())
+            .skip(1)
+            .map(|((tag, _), block)| (i64_type.const_int(*tag, false), *block))
+            .collect();
+        builder.build_switch(type_, blocks[0], &cases);
+
+        for ((_, name), block) in names.iter().zip(blocks.iter()) {
+            builder.position_at_end(*block);
+            let name_str = builder.build_global_string_ptr(name, "type_name_str");
+            builder.build_return(Some(&name_str.as_pointer_value()));
+        }
+    }
+
+    // string concat fn
+    {
+        let concat_fn_type = i8_ptr_type.fn_type(&[i8_ptr_type.into(), i8_ptr_type.into()], false);
+        let concat_fn = module.add_function("string_concat", concat_fn_type, None);
+
+        let strlen_fn = module.get_function("strlen").unwrap();
+        let gc_alloc_fn = module.get_function("gc_alloc").unwrap();
+        let memcpy_fn = module.get_function("memcpy").unwrap();
+
+        let entry = context.append_basic_block(concat_fn, "entry");
+        builder.position_at_end(entry);
+
+        let left = concat_fn.get_nth_param(0).unwrap().into_pointer_value();
+        let right = concat_fn.get_nth_param(1).unwrap().into_pointer_value();
+
+        let _1 = size_type.const_int(1, false);
+
+        let left_len = builder
+            .build_call(strlen_fn, &[left.into()], "")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let right_len = builder
+            .build_call(strlen_fn, &[right.into()], "")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        // copy the right string's null terminator as well
+        let right_size = builder.build_int_add(right_len, _1, "");
+        let size = builder.build_int_add(left_len, right_size, "");
+
+        let raw_kind = context.i32_type().const_int(GC_RAW, false);
+        let mem = builder
+            .build_call(gc_alloc_fn, &[raw_kind.into(), size.into()], "")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value();
+        builder.build_call(memcpy_fn, &[mem.into(), left.into(), left_len.into()], "");
+        let right_dest = unsafe { builder.build_in_bounds_gep(mem, &[left_len], "") };
+        builder.build_call(
+            memcpy_fn,
+            &[right_dest.into(), right.into(), right_size.into()],
+            "",
+        );
+
+        builder.build_return(Some(&mem));
+    }
+
+    // stringify fn, returning what display prints for a value as a heap string
+    {
+        let stringify_fn_type = i8_ptr_type.fn_type(&[i64_type.into()], false);
+        let stringify_fn = module.add_function("stringify", stringify_fn_type, None);
+
+        let concat_fn = module.get_function("string_concat").unwrap();
+        let number_to_string_fn = module.get_function("number_to_string").unwrap();
+        let value_type_fn = module.get_function("value_type").unwrap();
+        let pair_ptr_type = module
+            .get_struct_type("pair")
+            .unwrap()
+            .ptr_type(AddressSpace::Generic);
+
+        let entry = context.append_basic_block(stringify_fn, "entry");
+        let undefined_block = context.append_basic_block(stringify_fn, "undefined");
+        let boolean_block = context.append_basic_block(stringify_fn, "boolean");
+        let true_block = context.append_basic_block(stringify_fn, "true");
+        let false_block = context.append_basic_block(stringify_fn, "false");
+        let number_block = context.append_basic_block(stringify_fn, "number");
+        let function_block = context.append_basic_block(stringify_fn, "function");
+        let string_block = context.append_basic_block(stringify_fn, "string");
+        let pair_block = context.append_basic_block(stringify_fn, "pair");
+        let null_block = context.append_basic_block(stringify_fn, "null");
+        let copy_block = context.append_basic_block(stringify_fn, "copy");
+
+        let _0 = i32_type.const_int(0, false);
+        let _1 = i32_type.const_int(1, false);
+        let _2 = i32_type.const_int(2, false);
+
+        let concat = |left: PointerValue<'ctx>, right: PointerValue<'ctx>| {
+            builder
+                .build_call(concat_fn, &[left.into(), right.into()], "")
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_pointer_value()
+        };
+        let global_str = |value: &str| {
+            builder
+                .build_global_string_ptr(value, "stringify_str")
+                .as_pointer_value()
+        };
+
+        builder.position_at_end(entry);
+        let value = stringify_fn.get_first_param().unwrap().into_int_value();
+        let obj_type = builder
+            .build_call(value_type_fn, &[value.into()], "")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        builder.build_switch(
+            obj_type,
+            undefined_block,
+            &[
+                (i64_type.const_int(1, false), boolean_block),
+                (i64_type.const_int(2, false), number_block),
+                (i64_type.const_int(3, false), function_block),
+                (i64_type.const_int(4, false), string_block),
+                (i64_type.const_int(6, false), pair_block),
+                (i64_type.const_int(7, false), null_block),
+            ],
+        );
+
+        // the strings of the other values are constants or on the stack, so
+        // they are copied to the heap
+        let mut copies = vec![];
+        for &(block, string) in [
+            (undefined_block, "undefined"),
+            (true_block, "true"),
+            (false_block, "false"),
+            (function_block, "Function"),
+            (null_block, "null"),
+        ]
+        .iter()
+        {
+            builder.position_at_end(block);
+            copies.push((global_str(string), block));
+            builder.build_unconditional_branch(copy_block);
+        }
+
+        builder.position_at_end(boolean_block);
+        let bool_value = builder.build_int_truncate(value, bool_type, "");
+        builder.build_conditional_branch(bool_value, true_block, false_block);
+
+        builder.position_at_end(number_block);
+        let number_value = builder.build_bitcast(value, f64_type, "");
+        let buffer_size = size_type.const_int(NUMBER_BUFFER_SIZE, false);
+        let buffer = builder.build_array_alloca(i8_type, buffer_size, "number_buffer");
+        let number_str = builder
+            .build_call(number_to_string_fn, &[number_value, buffer.into()], "")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_pointer_value();
+        copies.push((number_str, number_block));
+        builder.build_unconditional_branch(copy_block);
+
+        builder.position_at_end(copy_block);
+        let string = builder.build_phi(i8_ptr_type, "string");
+        for (value, block) in copies.iter() {
+            string.add_incoming(&[(value, *block)]);
+        }
+        let copy = concat(global_str(""), string.as_basic_value().into_pointer_value());
+        builder.build_return(Some(&copy));
+
+        // strings are quoted, like display prints them
+        builder.position_at_end(string_block);
+        let string_value = build_string_value(value, context, module, builder);
+        let quote = global_str("\"");
+        let quoted = concat(concat(quote, string_value), quote);
+        builder.build_return(Some(&quoted));
+
+        // pairs are in box notation, stringifying the head and tail recursively
+        builder.position_at_end(pair_block);
+        let obj = builder.build_and(value, i64_type.const_int(!POINTER_BITS, false), "");
+        let obj = builder.build_int_to_ptr(obj, pair_ptr_type, "");
+        let head_ptr = unsafe { builder.build_in_bounds_gep(obj, &[_0, _1], "") };
+        let head = builder.build_load(head_ptr, "");
+        let tail_ptr = unsafe { builder.build_in_bounds_gep(obj, &[_0, _2], "") };
+        let tail = builder.build_load(tail_ptr, "");
+        let stringify = |value: BasicValueEnum<'ctx>| {
+            builder
+                .build_call(stringify_fn, &[value], "")
+                .try_as_basic_value()
+                .left()
+                .unwrap()
+                .into_pointer_value()
+        };
+        let head_str = stringify(head);
+        let tail_str = stringify(tail);
+        let result = concat(global_str("["), head_str);
+        let result = concat(result, global_str(", "));
+        let result = concat(result, tail_str);
+        let result = concat(result, global_str("]"));
+        builder.build_return(Some(&result));
+    }
+
+    // strict equality fn, comparing the types first and then the values
+    {
+        let strict_equal_fn_type = bool_type.fn_type(&[i64_type.into(), i64_type.into()], false);
+        let strict_equal_fn = module.add_function("strict_equal", strict_equal_fn_type, None);
+
+        let strcmp_fn = module.get_function("strcmp").unwrap();
+        let value_type_fn = module.get_function("value_type").unwrap();
+
+        let entry = context.append_basic_block(strict_equal_fn, "entry");
+        let dispatch_block = context.append_basic_block(strict_equal_fn, "dispatch");
+        let different_block = context.append_basic_block(strict_equal_fn, "different");
+        let identical_block = context.append_basic_block(strict_equal_fn, "identical");
+        let number_block = context.append_basic_block(strict_equal_fn, "number");
+        let string_block = context.append_basic_block(strict_equal_fn, "string");
+
+        let _0 = context.i32_type().const_int(0, false);
+        let _1 = context.i32_type().const_int(1, false);
 
-        let entry = context.append_basic_block(error_fn, "entry");
         builder.position_at_end(entry);
+        let left = strict_equal_fn.get_nth_param(0).unwrap().into_int_value();
+        let right = strict_equal_fn.get_nth_param(1).unwrap().into_int_value();
+        let left_type = builder
+            .build_call(value_type_fn, &[left.into()], "")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let right_type = builder
+            .build_call(value_type_fn, &[right.into()], "")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let same_type = builder.build_int_compare(IntPredicate::EQ, left_type, right_type, "");
+        builder.build_conditional_branch(same_type, dispatch_block, different_block);
+
+        builder.position_at_end(dispatch_block);
+        builder.build_switch(
+            left_type,
+            identical_block,
+            &[
+                (i64_type.const_int(2, false), number_block),
+                (i64_type.const_int(4, false), string_block),
+            ],
+        );
+
+        builder.position_at_end(different_block);
+        builder.build_return(Some(&bool_type.const_zero()));
+
+        // undefined, booleans and functions are equal only if they are the
+        // same value, the latter being the same closure
+        builder.position_at_end(identical_block);
+        let result = builder.build_int_compare(IntPredicate::EQ, left, right, "");
+        builder.build_return(Some(&result));
+
+        // NaN is not equal to itself, while 0 and -0 are equal
+        builder.position_at_end(number_block);
+        let left_number = builder.build_bitcast(left, f64_type, "").into_float_value();
+        let right_number = builder
+            .build_bitcast(right, f64_type, "")
+            .into_float_value();
+        let result =
+            builder.build_float_compare(FloatPredicate::OEQ, left_number, right_number, "");
+        builder.build_return(Some(&result));
+
+        builder.position_at_end(string_block);
+        let not_pointer_bits = i64_type.const_int(!POINTER_BITS, false);
+        let left_address = builder.build_and(left, not_pointer_bits, "");
+        let left_obj = builder.build_int_to_ptr(left_address, source_obj_ptr_type, "");
+        let right_address = builder.build_and(right, not_pointer_bits, "");
+        let right_obj = builder.build_int_to_ptr(right_address, source_obj_ptr_type, "");
+        let left_value_ptr = unsafe { builder.build_in_bounds_gep(left_obj, &[_0, _1], "") };
+        let left_value = builder.build_load(left_value_ptr, "").into_int_value();
+        let right_value_ptr = unsafe { builder.build_in_bounds_gep(right_obj, &[_0, _1], "") };
+        let right_value = builder.build_load(right_value_ptr, "").into_int_value();
+        let left_str = builder.build_int_to_ptr(left_value, i8_ptr_type, "");
+        let right_str = builder.build_int_to_ptr(right_value, i8_ptr_type, "");
+        let cmp = builder
+            .build_call(strcmp_fn, &[left_str.into(), right_str.into()], "")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let result = builder.build_int_compare(IntPredicate::EQ, cmp, i32_type.const_zero(), "");
+        builder.build_return(Some(&result));
+    }
+
+    // trampoline fn, making tail calls until a value is returned
+    {
+        let trampoline_fn_type = i64_type.fn_type(&[i64_type.into()], false);
+        let trampoline_fn = module.add_function("trampoline", trampoline_fn_type, None);
+
+        let value_type_fn = module.get_function("value_type").unwrap();
+
+        let entry = context.append_basic_block(trampoline_fn, "entry");
+        let loop_block = context.append_basic_block(trampoline_fn, "loop");
+        let call_block = context.append_basic_block(trampoline_fn, "call");
+        let end_block = context.append_basic_block(trampoline_fn, "end");
+
+        let _0 = context.i32_type().const_int(0, false);
+        let _1 = context.i32_type().const_int(1, false);
+        let _2 = context.i32_type().const_int(2, false);
+
+        let roots_top = module
+            .get_global("gc_roots_top")
+            .unwrap()
+            .as_pointer_value();
+        let push_root_fn = module.get_function("gc_push_root").unwrap();
+
+        builder.position_at_end(entry);
+        let initial = trampoline_fn.get_first_param().unwrap();
+        let saved_roots_top = builder.build_load(roots_top, "");
+        builder.build_unconditional_branch(loop_block);
+
+        builder.position_at_end(loop_block);
+        let value = builder.build_phi(i64_type, "value");
+        let value_word = value.as_basic_value().into_int_value();
+        let type_ = builder
+            .build_call(value_type_fn, &[value_word.into()], "")
+            .try_as_basic_value()
+            .left()
+            .unwrap()
+            .into_int_value();
+        let is_tail_call =
+            builder.build_int_compare(IntPredicate::EQ, type_, i64_type.const_int(5, false), "");
+        builder.build_conditional_branch(is_tail_call, call_block, end_block);
+
+        builder.position_at_end(call_block);
+        let address = builder.build_and(value_word, i64_type.const_int(!POINTER_BITS, false), "");
+        let tail_call = builder.build_int_to_ptr(address, tail_call_ptr_type, "");
+        let callee_ptr = unsafe { builder.build_in_bounds_gep(tail_call, &[_0, _1], "") };
+        let closure = builder.build_load(callee_ptr, "").into_pointer_value();
+        let params_ptr = unsafe { builder.build_in_bounds_gep(tail_call, &[_0, _2], "") };
+        let params = builder.build_load(params_ptr, "");
+        let env_ptr = unsafe { builder.build_in_bounds_gep(closure, &[_0, _1], "") };
+        let env = builder.build_load(env_ptr, "");
+        let fn_ptr = unsafe { builder.build_in_bounds_gep(closure, &[_0, _2], "") };
+        let fn_ = builder.build_load(fn_ptr, "").into_pointer_value();
+        let result = builder
+            .build_call(fn_, &[env, params], "")
+            .try_as_basic_value()
+            .left()
+            .unwrap();
+        // drop the roots of the previous call, so that the loop runs in constant space
+        builder.build_store(roots_top, saved_roots_top);
+        builder.build_call(push_root_fn, &[result], "");
+        builder.build_unconditional_branch(loop_block);
+
+        value.add_incoming(&[(&initial, entry), (&result, call_block)]);
+
+        builder.position_at_end(end_block);
+        builder.build_return(Some(&value_word));
+    }
+
+    // error fn
+    {
+        let error_fn = module.get_function("error").unwrap();
 
-        let error_str = builder.build_global_string_ptr("Type mismatch\n", "error_fmt_str");
         let exit_fn = module.get_function("exit").unwrap();
         let printf_fn = module.get_function("printf").unwrap();
+        let print_value_fn = module.get_function("print_value").unwrap();
+        let type_name_fn = module.get_function("type_name").unwrap();
+
+        let entry = context.append_basic_block(error_fn, "entry");
+        let location_block = context.append_basic_block(error_fn, "location");
+        let dispatch_block = context.append_basic_block(error_fn, "dispatch");
+        let unary_block = context.append_basic_block(error_fn, "unary");
+        let left_block = context.append_basic_block(error_fn, "left");
+        let right_block = context.append_basic_block(error_fn, "right");
+        let call_block = context.append_basic_block(error_fn, "call");
+        let arity_block = context.append_basic_block(error_fn, "arity");
+        let condition_block = context.append_basic_block(error_fn, "condition");
+        let stack_overflow_block = context.append_basic_block(error_fn, "stack_overflow");
+        let end_block = context.append_basic_block(error_fn, "end");
+
+        builder.position_at_end(entry);
+        let kind = error_fn.get_nth_param(0).unwrap().into_int_value();
+        let a = error_fn.get_nth_param(1).unwrap().into_int_value();
+        let b = error_fn.get_nth_param(2).unwrap().into_int_value();
+        // The column is passed along but not printed, matching js-slang's format.
+        let line = error_fn.get_nth_param(3).unwrap().into_int_value();
+        let has_location =
+            builder.build_int_compare(IntPredicate::NE, line, i32_type.const_zero(), "");
+        builder.build_conditional_branch(has_location, location_block, dispatch_block);
+
+        builder.position_at_end(location_block);
+        let location_str = builder.build_global_string_ptr("Line %d: ", "location_fmt_str");
+        builder.build_call(
+            printf_fn,
+            &[location_str.as_pointer_value().into(), line.into()],
+            "",
+        );
+        builder.build_unconditional_branch(dispatch_block);
+
+        builder.position_at_end(dispatch_block);
+        builder.build_switch(
+            kind,
+            end_block,
+            &[
+                (i32_type.const_int(UNARY_TYPE_ERROR, false), unary_block),
+                (i32_type.const_int(LEFT_TYPE_ERROR, false), left_block),
+                (i32_type.const_int(RIGHT_TYPE_ERROR, false), right_block),
+                (
+                    i32_type.const_int(CALL_NON_FUNCTION_ERROR, false),
+                    call_block,
+                ),
+                (i32_type.const_int(ARITY_ERROR, false), arity_block),
+                (
+                    i32_type.const_int(CONDITION_TYPE_ERROR, false),
+                    condition_block,
+                ),
+                (
+                    i32_type.const_int(STACK_OVERFLOW_ERROR, false),
+                    stack_overflow_block,
+                ),
+            ],
+        );
+
+        // type errors, where a is the expected and b the actual type
+        for (block, fmt_str) in [
+            (unary_block, "Expected %s, got %s.\n"),
+            (
+                left_block,
+                "Expected %s on left hand side of operation, got %s.\n",
+            ),
+            (
+                right_block,
+                "Expected %s on right hand side of operation, got %s.\n",
+            ),
+            (condition_block, "Expected %s as condition, got %s.\n"),
+        ]
+        .iter()
+        {
+            builder.position_at_end(*block);
+            let expected = builder
+                .build_call(type_name_fn, &[a.into()], "")
+                .try_as_basic_value()
+                .left()
+                .unwrap();
+            let actual = builder
+                .build_call(type_name_fn, &[b.into()], "")
+                .try_as_basic_value()
+                .left()
+                .unwrap();
+            let error_str = builder.build_global_string_ptr(fmt_str, "type_error_fmt_str");
+            builder.build_call(
+                printf_fn,
+                &[error_str.as_basic_value_enum(), expected, actual],
+                "",
+            );
+            builder.build_unconditional_branch(end_block);
+        }
+
+        // calling a non-function, where b is the callee
+        {
+            builder.position_at_end(call_block);
+            let prefix_str =
+                builder.build_global_string_ptr("Calling non-function value ", "call_error_str");
+            builder.build_call(printf_fn, &[prefix_str.as_basic_value_enum()], "");
+            builder.build_call(print_value_fn, &[b.into()], "");
+            let suffix_str = builder.build_global_string_ptr(".\n", "call_error_suffix_str");
+            builder.build_call(printf_fn, &[suffix_str.as_basic_value_enum()], "");
+            builder.build_unconditional_branch(end_block);
+        }
+
+        // arity mismatch, where a is the expected and b the actual number of arguments
+        {
+            builder.position_at_end(arity_block);
+            let error_str = builder.build_global_string_ptr(
+                "Expected %lld arguments, but got %lld.\n",
+                "arity_error_fmt_str",
+            );
+            builder.build_call(
+                printf_fn,
+                &[error_str.as_basic_value_enum(), a.into(), b.into()],
+                "",
+            );
+            builder.build_unconditional_branch(end_block);
+        }
 
+        // calls are nested more than MAX_CALL_DEPTH deep, or the shadow stack
+        // of roots is full, which only deep recursion does
+        {
+            builder.position_at_end(stack_overflow_block);
+            let error_str = builder.build_global_string_ptr(
+                "Maximum call stack size exceeded.\n",
+                "stack_overflow_error_str",
+            );
+            builder.build_call(printf_fn, &[error_str.as_basic_value_enum()], "");
+            builder.build_unconditional_branch(end_block);
+        }
+
+        builder.position_at_end(end_block);
         let _1 = context.i32_type().const_int(1, false);
-        builder.build_call(printf_fn, &[error_str.as_basic_value_enum()], "");
         builder.build_call(exit_fn, &[_1.into()], "");
         builder.build_return(None);
     }
@@ -1,16 +1,155 @@
-use anyhow::Error;
+use anyhow::{anyhow, Error};
 
-use std::io::{stdin, stdout, Read, Write};
+use std::{
+    env::args,
+    fs::read_to_string,
+    io::{stdin, stdout, Read, Write},
+    path::PathBuf,
+    process::exit,
+};
 
-use sourcec::compile;
+use sourcec::{
+    compile_source_with_options, compile_with_options,
+    diagnostics::{render, Diagnostic, ErrorFormat},
+    emit, emit_source, run, run_source, CompileErrors, CompileOptions, Emit,
+};
+
+const USAGE: &str = "usage: sourcec [--target <triple>] [--cpu <cpu>] [--features <features>] \
+                     [--emit <llvm-ir,llvm-bc,asm,obj>] [-o <path>] [-O0|-O1|-O2|-O3] [--error-format <human|json>] [file]
+       sourcec run [-O0|-O1|-O2|-O3] [--error-format <human|json>] [file]";
+
+/// Prints the errors in a program as diagnostics on stderr and exits, or
+/// returns what compiling it produced.
+fn report<T>(
+    result: Result<T, CompileErrors>,
+    format: ErrorFormat,
+    path: &Option<String>,
+    source: &str,
+) -> T {
+    result.unwrap_or_else(|errors| {
+        let diagnostics: Vec<Diagnostic> = errors.0.iter().map(Diagnostic::from).collect();
+        // only the Source text makes sense as snippets, not its ESTree
+        let (name, source) = match path {
+            Some(path) => (path.as_str(), Some(source)),
+            None => ("<stdin>", None),
+        };
+        eprintln!("{}", render(&diagnostics, format, name, source).trim_end());
+        exit(1);
+    })
+}
 
 fn main() -> Result<(), Error> {
-    let mut es_str = String::new();
-    stdin().read_to_string(&mut es_str)?;
+    let mut options = CompileOptions::default();
+    let mut path = None;
+    let mut kinds = vec![];
+    let mut output = None;
+    let mut targeted = false;
+    let mut error_format = ErrorFormat::Human;
+
+    // `sourcec run` compiles for the host and runs the program in-process
+    let mut args = args().skip(1).peekable();
+    let run_mode = args.peek().map_or(false, |arg| arg == "run");
+    if run_mode {
+        args.next();
+    }
+
+    while let Some(arg) = args.next() {
+        // flags take their value either as `--flag value` or `--flag=value`
+        let mut parts = arg.splitn(2, '=');
+        let flag = parts.next().unwrap();
+        let inline_value = parts.next().map(String::from);
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| anyhow!("missing value for {}\n{}", flag, USAGE))
+        };
+
+        match flag {
+            "--target" => {
+                options.target_triple = value()?;
+                targeted = true;
+            }
+            "--cpu" => {
+                options.cpu = value()?;
+                targeted = true;
+            }
+            "--features" => {
+                options.features = value()?;
+                targeted = true;
+            }
+            "--emit" => {
+                for kind in value()?.split(',') {
+                    kinds.push(kind.parse::<Emit>()?);
+                }
+            }
+            "-o" => output = Some(PathBuf::from(value()?)),
+            "-O0" | "-O1" | "-O2" | "-O3" => options.opt_level = flag[2..].parse()?,
+            "--error-format" => error_format = value()?.parse()?,
+            _ if flag.starts_with('-') => return Err(anyhow!("unknown flag {}\n{}", flag, USAGE)),
+            _ => path = Some(arg.clone()),
+        }
+    }
+
+    // With a file argument we parse the Source program ourselves, otherwise we
+    // expect its ESTree JSON on stdin.
+    let source = match &path {
+        Some(path) => read_to_string(path)?,
+        None => {
+            let mut es_str = String::new();
+            stdin().read_to_string(&mut es_str)?;
+            es_str
+        }
+    };
+
+    if run_mode {
+        if targeted || !kinds.is_empty() || output.is_some() {
+            return Err(anyhow!(
+                "run only takes an optimization level, an error format and a file\n{}",
+                USAGE
+            ));
+        }
+        let result = match path {
+            Some(_) => run_source(&source, options.opt_level),
+            None => run(&source, options.opt_level),
+        };
+        let exit_code = report(result, error_format, &path, &source);
+        exit(exit_code);
+    }
 
-    let ll = compile(&es_str)?;
+    // Without --emit the IR goes to stdout unless an output path is given.
+    if kinds.is_empty() && output.is_none() {
+        let result = match path {
+            Some(_) => compile_source_with_options(&source, &options),
+            None => compile_with_options(&source, &options),
+        };
+        let ll = report(result, error_format, &path, &source);
+        stdout().write(ll.as_bytes())?;
+        return Ok(());
+    }
+    if kinds.is_empty() {
+        kinds.push(Emit::LlvmIr);
+    }
 
-    stdout().write(ll.as_bytes())?;
+    // A single artifact is written to the output path as is, while multiple
+    // artifacts share its stem, or the input's if there is none.
+    let artifacts: Vec<(Emit, PathBuf)> = match (&output, kinds.len()) {
+        (Some(output), 1) => vec![(kinds[0], output.clone())],
+        _ => {
+            let stem = output
+                .or_else(|| path.clone().map(PathBuf::from))
+                .unwrap_or_else(|| PathBuf::from("main"));
+            kinds
+                .iter()
+                .map(|kind| (*kind, stem.with_extension(kind.extension())))
+                .collect()
+        }
+    };
 
+    let result = match path {
+        Some(_) => emit_source(&source, &options, &artifacts),
+        None => emit(&source, &options, &artifacts),
+    };
+    report(result, error_format, &path, &source);
     Ok(())
 }
@@ -0,0 +1,696 @@
+use crate::error::CompileError;
+use serde_json::{json, Value};
+
+use crate::lexer::{tokenize, Position, Token, TokenKind};
+
+/// Parses a Source §2 program into the same ESTree JSON shape that js-slang
+/// produces, so that it can be fed to the code generator unchanged. Parsing
+/// resumes after a syntax error with the next statement, so that all of them
+/// are returned.
+pub(crate) fn parse(source: &str) -> Result<Value, Vec<CompileError>> {
+    let mut parser = Parser {
+        tokens: tokenize(source).map_err(|err| vec![err])?,
+        index: 0,
+        errors: vec![],
+    };
+    let program = parser.program();
+
+    if parser.errors.is_empty() {
+        Ok(program)
+    } else {
+        Err(parser.errors)
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    index: usize,
+    errors: Vec<CompileError>,
+}
+
+fn loc(start: Position, end: Position) -> Value {
+    json!({
+        "start": { "line": start.line, "column": start.column },
+        "end": { "line": end.line, "column": end.column },
+    })
+}
+
+fn syntax_error(position: Position, message: String) -> CompileError {
+    CompileError::Syntax {
+        message,
+        position: position.into(),
+    }
+}
+
+fn binary_precedence(operator: &str) -> Option<u8> {
+    match operator {
+        "||" => Some(1),
+        "&&" => Some(2),
+        "===" | "!==" => Some(3),
+        "<" | ">" | "<=" | ">=" => Some(4),
+        "+" | "-" => Some(5),
+        "*" | "/" | "%" => Some(6),
+        _ => None,
+    }
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.index]
+    }
+
+    fn peek_kind_at(&self, offset: usize) -> &TokenKind {
+        let index = (self.index + offset).min(self.tokens.len() - 1);
+        &self.tokens[index].kind
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.index].clone();
+        if token.kind != TokenKind::Eof {
+            self.index += 1;
+        }
+        token
+    }
+
+    fn last_end(&self) -> Position {
+        self.tokens[self.index.saturating_sub(1)].end
+    }
+
+    fn is_punct(&self, punct: &'static str) -> bool {
+        self.peek().kind == TokenKind::Punct(punct)
+    }
+
+    fn is_keyword(&self, keyword: &'static str) -> bool {
+        self.peek().kind == TokenKind::Keyword(keyword)
+    }
+
+    fn unexpected(&self) -> CompileError {
+        let token = self.peek();
+        let description = match &token.kind {
+            TokenKind::Number(_, raw) => format!("number {}", raw),
+            TokenKind::String(s) => format!("string {:?}", s),
+            TokenKind::Name(name) => format!("name {}", name),
+            TokenKind::Keyword(keyword) => format!("keyword {}", keyword),
+            TokenKind::Punct(punct) => format!("token {}", punct),
+            TokenKind::Eof => "end of input".into(),
+        };
+        syntax_error(
+            token.start,
+            format!(
+                "SyntaxError: Unexpected {} ({}:{})",
+                description, token.start.line, token.start.column
+            ),
+        )
+    }
+
+    fn expect_punct(&mut self, punct: &'static str) -> Result<Token, CompileError> {
+        if self.is_punct(punct) {
+            Ok(self.next())
+        } else if punct == ";" {
+            Err(syntax_error(
+                self.last_end(),
+                "Missing semicolon at the end of statement".into(),
+            ))
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &'static str) -> Result<Token, CompileError> {
+        if self.is_keyword(keyword) {
+            Ok(self.next())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    fn identifier(&mut self) -> Result<Value, CompileError> {
+        match self.peek().kind.clone() {
+            TokenKind::Name(name) => {
+                let token = self.next();
+                Ok(json!({
+                    "type": "Identifier",
+                    "name": name,
+                    "loc": loc(token.start, token.end),
+                }))
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    /// Parses a statement, or records its syntax error and skips past it.
+    fn recovering_statement(&mut self) -> Option<Value> {
+        let start = self.index;
+        match self.statement() {
+            Ok(stmt) => Some(stmt),
+            Err(err) => {
+                self.errors.push(err);
+                self.synchronize();
+                // e.g. a stray `}`, which no statement starts with
+                if self.index == start {
+                    self.next();
+                }
+                None
+            }
+        }
+    }
+
+    /// Skips to the end of the statement a syntax error is in, i.e. past its
+    /// `;` or its block, but not past the `}` of a block it's in.
+    fn synchronize(&mut self) {
+        let mut depth = 0;
+        loop {
+            match self.peek().kind.clone() {
+                TokenKind::Eof => break,
+                TokenKind::Punct(";") if depth == 0 => {
+                    self.next();
+                    break;
+                }
+                TokenKind::Punct("{") => depth += 1,
+                TokenKind::Punct("}") if depth == 0 => break,
+                TokenKind::Punct("}") => {
+                    depth -= 1;
+                    self.next();
+                    // an `if` statement goes on with its `else` block
+                    if depth == 0 && !self.is_keyword("else") {
+                        break;
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            self.next();
+        }
+    }
+
+    fn program(&mut self) -> Value {
+        let start = self.peek().start;
+        let mut body = vec![];
+        while self.peek().kind != TokenKind::Eof {
+            body.extend(self.recovering_statement());
+        }
+
+        json!({
+            "type": "Program",
+            "sourceType": "script",
+            "body": body,
+            "loc": loc(start, self.last_end()),
+        })
+    }
+
+    fn statement(&mut self) -> Result<Value, CompileError> {
+        match &self.peek().kind {
+            TokenKind::Keyword("const") => self.var_decl(),
+            TokenKind::Keyword("function") => self.fn_decl(),
+            TokenKind::Keyword("return") => self.return_stmt(),
+            TokenKind::Keyword("if") => self.if_stmt(),
+            TokenKind::Punct("{") => self.block_stmt(),
+            TokenKind::Keyword(keyword) if !["false", "null", "true"].contains(keyword) => {
+                Err(syntax_error(
+                    self.peek().start,
+                    format!("Unsupported keyword {} in Source §2", keyword),
+                ))
+            }
+            _ => self.expr_stmt(),
+        }
+    }
+
+    fn var_decl(&mut self) -> Result<Value, CompileError> {
+        let start = self.expect_keyword("const")?.start;
+        let id_start = self.peek().start;
+        let id = self.identifier()?;
+        self.expect_punct("=")?;
+        let init = self.expression()?;
+        let declarator_end = self.last_end();
+        self.expect_punct(";")?;
+
+        Ok(json!({
+            "type": "VariableDeclaration",
+            "declarations": [{
+                "type": "VariableDeclarator",
+                "id": id,
+                "init": init,
+                "loc": loc(id_start, declarator_end),
+            }],
+            "kind": "const",
+            "loc": loc(start, self.last_end()),
+        }))
+    }
+
+    fn fn_decl(&mut self) -> Result<Value, CompileError> {
+        let start = self.expect_keyword("function")?.start;
+        let id = self.identifier()?;
+        let params = self.params()?;
+        let body = self.block_stmt()?;
+
+        Ok(json!({
+            "type": "FunctionDeclaration",
+            "id": id,
+            "params": params,
+            "body": body,
+            "expression": false,
+            "generator": false,
+            "async": false,
+            "loc": loc(start, self.last_end()),
+        }))
+    }
+
+    fn params(&mut self) -> Result<Vec<Value>, CompileError> {
+        self.expect_punct("(")?;
+        let mut params = vec![];
+        if !self.is_punct(")") {
+            loop {
+                params.push(self.identifier()?);
+                if !self.is_punct(",") {
+                    break;
+                }
+                self.next();
+            }
+        }
+        self.expect_punct(")")?;
+
+        Ok(params)
+    }
+
+    fn return_stmt(&mut self) -> Result<Value, CompileError> {
+        let start = self.expect_keyword("return")?.start;
+        let argument = self.expression()?;
+        self.expect_punct(";")?;
+
+        Ok(json!({
+            "type": "ReturnStatement",
+            "argument": argument,
+            "loc": loc(start, self.last_end()),
+        }))
+    }
+
+    fn if_stmt(&mut self) -> Result<Value, CompileError> {
+        let start = self.expect_keyword("if")?.start;
+        self.expect_punct("(")?;
+        let test = self.expression()?;
+        self.expect_punct(")")?;
+        let consequent = self.block_stmt()?;
+        if !self.is_keyword("else") {
+            return Err(syntax_error(
+                start,
+                "Missing \"else\" in \"if-else\" statement.".into(),
+            ));
+        }
+        self.next();
+        let alternate = if self.is_keyword("if") {
+            self.if_stmt()?
+        } else {
+            self.block_stmt()?
+        };
+
+        Ok(json!({
+            "type": "IfStatement",
+            "test": test,
+            "consequent": consequent,
+            "alternate": alternate,
+            "loc": loc(start, self.last_end()),
+        }))
+    }
+
+    fn block_stmt(&mut self) -> Result<Value, CompileError> {
+        let start = self.expect_punct("{")?.start;
+        let mut body = vec![];
+        while !self.is_punct("}") {
+            if self.peek().kind == TokenKind::Eof {
+                return Err(self.unexpected());
+            }
+            body.extend(self.recovering_statement());
+        }
+        self.next();
+
+        Ok(json!({
+            "type": "BlockStatement",
+            "body": body,
+            "loc": loc(start, self.last_end()),
+        }))
+    }
+
+    fn expr_stmt(&mut self) -> Result<Value, CompileError> {
+        let start = self.peek().start;
+        let expression = self.expression()?;
+        self.expect_punct(";")?;
+
+        Ok(json!({
+            "type": "ExpressionStatement",
+            "expression": expression,
+            "loc": loc(start, self.last_end()),
+        }))
+    }
+
+    fn expression(&mut self) -> Result<Value, CompileError> {
+        if self.is_arrow_fn_ahead() {
+            return self.arrow_fn_expr();
+        }
+
+        let start = self.peek().start;
+        let left = self.ternary_expr()?;
+        if !self.is_punct("=") {
+            return Ok(left);
+        }
+        if left["type"] != "Identifier" {
+            return Err(syntax_error(
+                self.peek().start,
+                "Invalid assignment target".into(),
+            ));
+        }
+        self.next();
+        let right = self.expression()?;
+
+        Ok(json!({
+            "type": "AssignmentExpression",
+            "operator": "=",
+            "left": left,
+            "right": right,
+            "loc": loc(start, self.last_end()),
+        }))
+    }
+
+    /// Arrow functions are either `x => ...` or `(x, y) => ...`, so in the
+    /// latter case we need to look past the matching parenthesis.
+    fn is_arrow_fn_ahead(&self) -> bool {
+        match self.peek_kind_at(0) {
+            TokenKind::Name(_) => self.peek_kind_at(1) == &TokenKind::Punct("=>"),
+            TokenKind::Punct("(") => {
+                let mut offset = 1;
+                loop {
+                    match self.peek_kind_at(offset) {
+                        TokenKind::Punct(")") => {
+                            break self.peek_kind_at(offset + 1) == &TokenKind::Punct("=>")
+                        }
+                        TokenKind::Name(_) | TokenKind::Punct(",") => offset += 1,
+                        _ => break false,
+                    }
+                }
+            }
+            _ => false,
+        }
+    }
+
+    fn arrow_fn_expr(&mut self) -> Result<Value, CompileError> {
+        let start = self.peek().start;
+        let params = if self.is_punct("(") {
+            self.params()?
+        } else {
+            vec![self.identifier()?]
+        };
+        self.expect_punct("=>")?;
+        let (body, is_expression) = if self.is_punct("{") {
+            (self.block_stmt()?, false)
+        } else {
+            (self.expression()?, true)
+        };
+
+        Ok(json!({
+            "type": "ArrowFunctionExpression",
+            "id": null,
+            "params": params,
+            "body": body,
+            "expression": is_expression,
+            "generator": false,
+            "async": false,
+            "loc": loc(start, self.last_end()),
+        }))
+    }
+
+    fn ternary_expr(&mut self) -> Result<Value, CompileError> {
+        let start = self.peek().start;
+        let test = self.binary_expr(1)?;
+        if !self.is_punct("?") {
+            return Ok(test);
+        }
+        self.next();
+        let consequent = self.expression()?;
+        self.expect_punct(":")?;
+        let alternate = self.expression()?;
+
+        Ok(json!({
+            "type": "ConditionalExpression",
+            "test": test,
+            "consequent": consequent,
+            "alternate": alternate,
+            "loc": loc(start, self.last_end()),
+        }))
+    }
+
+    /// Precedence climbing over the left-associative binary and logical
+    /// operators.
+    fn binary_expr(&mut self, min_precedence: u8) -> Result<Value, CompileError> {
+        let start = self.peek().start;
+        let mut left = self.unary_expr()?;
+
+        loop {
+            let operator = match &self.peek().kind {
+                TokenKind::Punct(punct) => *punct,
+                _ => break,
+            };
+            if operator == "==" || operator == "!=" {
+                return Err(syntax_error(
+                    self.peek().start,
+                    format!("Use {}= instead of {}", operator, operator),
+                ));
+            }
+            let precedence = match binary_precedence(operator) {
+                Some(precedence) if precedence >= min_precedence => precedence,
+                _ => break,
+            };
+            self.next();
+            let right = self.binary_expr(precedence + 1)?;
+            let type_ = if operator == "&&" || operator == "||" {
+                "LogicalExpression"
+            } else {
+                "BinaryExpression"
+            };
+            left = json!({
+                "type": type_,
+                "operator": operator,
+                "left": left,
+                "right": right,
+                "loc": loc(start, self.last_end()),
+            });
+        }
+
+        Ok(left)
+    }
+
+    fn unary_expr(&mut self) -> Result<Value, CompileError> {
+        let start = self.peek().start;
+        let operator = match &self.peek().kind {
+            TokenKind::Punct(punct) if *punct == "!" || *punct == "-" => *punct,
+            _ => return self.call_expr(),
+        };
+        self.next();
+        let argument = self.unary_expr()?;
+
+        Ok(json!({
+            "type": "UnaryExpression",
+            "operator": operator,
+            "prefix": true,
+            "argument": argument,
+            "loc": loc(start, self.last_end()),
+        }))
+    }
+
+    fn call_expr(&mut self) -> Result<Value, CompileError> {
+        let start = self.peek().start;
+        let mut callee = self.primary_expr()?;
+
+        while self.is_punct("(") {
+            self.next();
+            let mut arguments = vec![];
+            if !self.is_punct(")") {
+                loop {
+                    arguments.push(self.expression()?);
+                    if !self.is_punct(",") {
+                        break;
+                    }
+                    self.next();
+                }
+            }
+            self.expect_punct(")")?;
+            callee = json!({
+                "type": "CallExpression",
+                "callee": callee,
+                "arguments": arguments,
+                "optional": false,
+                "loc": loc(start, self.last_end()),
+            });
+        }
+
+        Ok(callee)
+    }
+
+    fn primary_expr(&mut self) -> Result<Value, CompileError> {
+        let token = self.peek().clone();
+        let literal = |value: Value| {
+            json!({
+                "type": "Literal",
+                "value": value,
+                "loc": loc(token.start, token.end),
+            })
+        };
+
+        match &token.kind {
+            TokenKind::Number(n, raw) => {
+                self.next();
+                // JSON has no Infinity, so like js-slang's ESTree after
+                // JSON.stringify, a literal too large for a number has a null
+                // value, and its raw text tells it apart from `null`
+                match serde_json::Number::from_f64(*n) {
+                    Some(value) => Ok(literal(Value::Number(value))),
+                    None => Ok(json!({
+                        "type": "Literal",
+                        "value": null,
+                        "raw": raw,
+                        "loc": loc(token.start, token.end),
+                    })),
+                }
+            }
+            TokenKind::String(s) => {
+                self.next();
+                Ok(literal(Value::String(s.clone())))
+            }
+            TokenKind::Keyword("true") => {
+                self.next();
+                Ok(literal(Value::Bool(true)))
+            }
+            TokenKind::Keyword("false") => {
+                self.next();
+                Ok(literal(Value::Bool(false)))
+            }
+            TokenKind::Keyword("null") => {
+                self.next();
+                Ok(literal(Value::Null))
+            }
+            TokenKind::Name(_) => self.identifier(),
+            TokenKind::Punct("(") => {
+                self.next();
+                let expression = self.expression()?;
+                self.expect_punct(")")?;
+                Ok(expression)
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use crate::error::CompileError;
+    use serde_json::{json, Value};
+
+    /// Returns the expression of the program's only statement, without the
+    /// locations of its nodes.
+    fn expr(source: &str) -> Value {
+        fn strip_locs(value: &mut Value) {
+            match value {
+                Value::Object(object) => {
+                    object.remove("loc");
+                    object.values_mut().for_each(strip_locs);
+                }
+                Value::Array(values) => values.iter_mut().for_each(strip_locs),
+                _ => {}
+            }
+        }
+
+        let mut program = parse(source).unwrap();
+        strip_locs(&mut program);
+        program["body"][0]["expression"].take()
+    }
+
+    fn syntax_error(source: &str) -> (String, usize, usize) {
+        match parse(source).unwrap_err().as_slice() {
+            [CompileError::Syntax { message, position }] => {
+                (message.clone(), position.line, position.column)
+            }
+            errors => panic!("expected a syntax error, got {:?}", errors),
+        }
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(expr("1 + 2 * 3;"), expr("1 + (2 * 3);"));
+        assert_ne!(expr("1 + 2 * 3;"), expr("(1 + 2) * 3;"));
+        assert_eq!(expr("-2 * 3;"), expr("(-2) * 3;"));
+        assert_eq!(
+            expr("1 + 2 < 4 && 2 * 2 === 4;"),
+            expr("((1 + 2) < 4) && ((2 * 2) === 4);")
+        );
+        assert_eq!(
+            expr("true || false && false;"),
+            expr("true || (false && false);")
+        );
+        assert_eq!(expr("true ? 1 : 2 + 3;"), expr("true ? 1 : (2 + 3);"));
+    }
+
+    #[test]
+    fn associativity() {
+        // binary operators and calls are left-associative
+        assert_eq!(expr("10 - 3 - 2;"), expr("(10 - 3) - 2;"));
+        assert_eq!(expr("64 / 4 / 2;"), expr("(64 / 4) / 2;"));
+        assert_eq!(expr("f(1)(2);"), expr("(f(1))(2);"));
+
+        // conditional expressions and arrow functions are right-associative
+        assert_eq!(expr("a ? b : c ? d : e;"), expr("a ? b : (c ? d : e);"));
+        assert_eq!(expr("x => y => x - y;"), expr("x => (y => x - y);"));
+    }
+
+    #[test]
+    fn literal_forms() {
+        assert_eq!(expr("1.5e3;")["value"], 1500.0);
+        assert_eq!(expr(".5;")["value"], 0.5);
+        assert_eq!(expr(r#"'it\'s' + "\"";"#)["left"]["value"], "it's");
+        assert_eq!(expr(r#"'it\'s' + "\"";"#)["right"]["value"], "\"");
+        assert_eq!(expr("false;")["value"], false);
+
+        // like in js-slang's ESTree, which is JSON without Infinity
+        assert_eq!(
+            expr("1e400;"),
+            json!({ "type": "Literal", "value": null, "raw": "1e400" })
+        );
+    }
+
+    #[test]
+    fn error_positions() {
+        assert_eq!(
+            syntax_error("const x = 1 +;"),
+            ("SyntaxError: Unexpected token ; (1:13)".into(), 1, 13)
+        );
+        assert_eq!(
+            syntax_error("1 2;"),
+            ("Missing semicolon at the end of statement".into(), 1, 1)
+        );
+        assert_eq!(syntax_error("1;\n1e;"), ("Invalid number".into(), 2, 2));
+        assert_eq!(
+            syntax_error("12abc;"),
+            ("Identifier directly after number".into(), 1, 2)
+        );
+        assert_eq!(
+            syntax_error("const s = \"abc;"),
+            ("Unterminated string constant".into(), 1, 15)
+        );
+        assert_eq!(
+            syntax_error("const b = 1 == 2;"),
+            ("Use === instead of ==".into(), 1, 12)
+        );
+        assert_eq!(
+            syntax_error("const b = 1 != 2;"),
+            ("Use !== instead of !=".into(), 1, 12)
+        );
+    }
+
+    #[test]
+    fn recovers_after_errors() {
+        let lines: Vec<_> = parse("1 2;\nconst x = 1;\nx +;")
+            .unwrap_err()
+            .iter()
+            .map(|err| err.loc().unwrap().start.line)
+            .collect();
+        assert_eq!(lines, [1, 3]);
+    }
+}
@@ -0,0 +1,113 @@
+// The list library of Source §2, following js-slang's. It is compiled ahead of
+// every program, so these functions are in scope without being declared, and
+// a program declaring one of their names shadows it. The functions call the
+// built-in functions directly, so they are unaffected by such declarations.
+
+// Returns whether xs and ys are structurally equal, i.e. the same value or
+// pairs of equal heads and tails.
+function equal(xs, ys) {
+    return is_pair(xs)
+        ? is_pair(ys) && equal(head(xs), head(ys)) && equal(tail(xs), tail(ys))
+        : xs === ys;
+}
+
+// Returns the length of the list xs, in constant stack.
+function length(xs) {
+    function iter(xs, count) {
+        return is_null(xs) ? count : iter(tail(xs), count + 1);
+    }
+    return iter(xs, 0);
+}
+
+// Returns the list of the results of applying f to each element of xs.
+function map(f, xs) {
+    return is_null(xs) ? null : pair(f(head(xs)), map(f, tail(xs)));
+}
+
+// Returns the list of f(0), f(1), ..., f(n - 1).
+function build_list(f, n) {
+    function build(i, built) {
+        return i < 0 ? built : build(i - 1, pair(f(i), built));
+    }
+    return build(n - 1, null);
+}
+
+// Applies f to each element of xs in order, and returns true.
+function for_each(f, xs) {
+    if (is_null(xs)) {
+        return true;
+    } else {
+        f(head(xs));
+        return for_each(f, tail(xs));
+    }
+}
+
+// Returns xs in box notation without spaces, e.g. [1,[2,null]].
+function list_to_string(xs) {
+    return is_null(xs)
+        ? "null"
+        : is_pair(xs)
+        ? "[" + list_to_string(head(xs)) + "," + list_to_string(tail(xs)) + "]"
+        : stringify(xs);
+}
+
+// Returns the elements of xs in reverse order, in constant stack.
+function reverse(xs) {
+    function iter(xs, reversed) {
+        return is_null(xs) ? reversed : iter(tail(xs), pair(head(xs), reversed));
+    }
+    return iter(xs, null);
+}
+
+// Returns the elements of xs followed by those of ys, sharing ys.
+function append(xs, ys) {
+    return is_null(xs) ? ys : pair(head(xs), append(tail(xs), ys));
+}
+
+// Returns the first tail of xs whose head is v, or null.
+function member(v, xs) {
+    return is_null(xs) ? null : v === head(xs) ? xs : member(v, tail(xs));
+}
+
+// Returns xs without the first element that is v.
+function remove(v, xs) {
+    return is_null(xs)
+        ? null
+        : v === head(xs)
+        ? tail(xs)
+        : pair(head(xs), remove(v, tail(xs)));
+}
+
+// Returns xs without any of the elements that are v.
+function remove_all(v, xs) {
+    return is_null(xs)
+        ? null
+        : v === head(xs)
+        ? remove_all(v, tail(xs))
+        : pair(head(xs), remove_all(v, tail(xs)));
+}
+
+// Returns the elements of xs for which pred returns true.
+function filter(pred, xs) {
+    return is_null(xs)
+        ? null
+        : pred(head(xs))
+        ? pair(head(xs), filter(pred, tail(xs)))
+        : filter(pred, tail(xs));
+}
+
+// Returns the list of the numbers from start to end, inclusive.
+function enum_list(start, end) {
+    return start > end ? null : pair(start, enum_list(start + 1, end));
+}
+
+// Returns the element of xs at index n, counting from 0.
+function list_ref(xs, n) {
+    return n === 0 ? head(xs) : list_ref(tail(xs), n - 1);
+}
+
+// Combines the elements of xs from the right with f, starting from initial,
+// e.g. accumulate(f, z, list(1, 2)) is f(1, f(2, z)).
+function accumulate(f, initial, xs) {
+    return is_null(xs) ? initial : f(head(xs), accumulate(f, initial, tail(xs)));
+}
@@ -0,0 +1,31 @@
+//! The prelude, a library written in Source that is compiled ahead of every
+//! program in a frame of its own, which is the parent of the program's
+//! top-level frame. For now it is the list library of Source §2, see
+//! `prelude.js`.
+
+use serde_json::Value;
+
+use crate::ast::{self, Program};
+use crate::parser;
+
+const PRELUDE: &str = include_str!("prelude.js");
+
+/// Parses the prelude. Its locations are dropped, as they are lines of
+/// `prelude.js` rather than of the program, so runtime errors in its functions
+/// are reported without a line.
+pub(crate) fn prelude() -> Program {
+    let mut value = parser::parse(PRELUDE).expect("the prelude is valid Source");
+    strip_locations(&mut value);
+    ast::from_estree(value).expect("the prelude is valid Source")
+}
+
+fn strip_locations(value: &mut Value) {
+    match value {
+        Value::Object(object) => {
+            object.remove("loc");
+            object.values_mut().for_each(strip_locations);
+        }
+        Value::Array(array) => array.iter_mut().for_each(strip_locations),
+        _ => {}
+    }
+}
@@ -1,135 +1,101 @@
 use std::rc::Rc;
 
+use crate::ast::{
+    BlockStatement, ExpressionStatement, FunctionDeclaration, IfStatement, ReturnStatement, Stmt,
+    VariableDeclaration,
+};
 use crate::env::Env;
-use crate::expr::{compile_expr, compile_fn_expr};
-use crate::helper::allocate_env;
-use anyhow::{anyhow, Error};
+use crate::error::CompileError;
+use crate::expr::{compile_condition, compile_expr, compile_fn_expr, compile_tail_expr};
+use crate::helper::{allocate_env, build_slot_ptr};
 use inkwell::{
     builder::Builder,
     context::Context,
     module::Module,
-    values::{FunctionValue, PointerValue},
-    AddressSpace,
+    values::{FunctionValue, IntValue},
 };
-use serde_json::Value;
 
 pub fn compile_stmt<'ctx>(
-    es_node: &Value,
+    stmt: &Stmt,
     env: Rc<Env<'ctx>>,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
     function: &FunctionValue<'ctx>,
-) -> Result<Option<PointerValue<'ctx>>, Error> {
-    let type_ = es_node.get("type").unwrap().as_str().unwrap();
-    // println!("{:?}", type_);
-    match type_ {
-        "VariableDeclaration" => {
-            compile_var_decl(es_node, env, context, module, builder, function).map(|_| None)
+) -> Result<Option<IntValue<'ctx>>, CompileError> {
+    match stmt {
+        Stmt::VariableDeclaration(decl) => {
+            compile_var_decl(decl, env, context, module, builder, function).map(|_| None)
         }
-        "ExpressionStatement" => {
-            compile_expr_stmt(es_node, env, context, module, builder, function).map(Some)
+        Stmt::ExpressionStatement(stmt) => {
+            compile_expr_stmt(stmt, env, context, module, builder, function).map(Some)
         }
-        "BlockStatement" => {
-            compile_block_stmt(es_node, env, context, module, builder, function).map(|_| None)
+        Stmt::BlockStatement(stmt) => {
+            compile_block_stmt(stmt, env, context, module, builder, function).map(|_| None)
         }
-        "IfStatement" => {
-            compile_if_stmt(es_node, env, context, module, builder, function).map(|_| None)
+        Stmt::IfStatement(stmt) => {
+            compile_if_stmt(stmt, env, context, module, builder, function).map(|_| None)
         }
-        "FunctionDeclaration" => {
-            compile_fn_decl(es_node, env, context, module, builder).map(|_| None)
+        Stmt::FunctionDeclaration(decl) => {
+            compile_fn_decl(decl, env, context, module, builder).map(|_| None)
         }
-        "ReturnStatement" => {
-            compile_return_stmt(es_node, env, context, module, builder, function).map(|_| None)
+        Stmt::ReturnStatement(stmt) => {
+            compile_return_stmt(stmt, env, context, module, builder, function).map(|_| None)
         }
-        _ => Err(anyhow!("stmt compile error")),
     }
 }
 
 pub fn compile_var_decl<'ctx>(
-    es_node: &Value,
+    decl: &VariableDeclaration,
     env: Rc<Env<'ctx>>,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
     function: &FunctionValue<'ctx>,
-) -> Result<(), Error> {
-    let declaration = &es_node.get("declarations").unwrap().as_array().unwrap()[0];
-    let name = declaration
-        .get("id")
-        .unwrap()
-        .get("name")
-        .unwrap()
-        .as_str()
-        .unwrap();
-    let init = declaration.get("init").unwrap();
-
-    let value = compile_expr(init, env.clone(), context, module, builder, function)?;
-    let mut frame = env.ptr.clone().unwrap();
-
-    let source_obj_type = module.get_struct_type("source_obj").unwrap();
-    let source_obj_ptr_type = source_obj_type.ptr_type(AddressSpace::Generic);
-    let source_obj_ptr_ptr_type = source_obj_ptr_type.ptr_type(AddressSpace::Generic);
-
-    let (jumps, offset) = env.lookup(name)?;
-
-    for _ in 0..jumps {
-        let tmp = builder
-            .build_bitcast(*frame, frame.get_type().ptr_type(AddressSpace::Generic), "")
-            .into_pointer_value();
-        frame = Rc::new(builder.build_load(tmp, "").into_pointer_value());
-    }
-
-    let frame_casted = builder
-        .build_bitcast(*frame, source_obj_ptr_ptr_type, "")
-        .into_pointer_value();
-    // SAFETY: Inherently unsafe
-    let ptr = unsafe {
-        builder.build_in_bounds_gep(
-            frame_casted,
-            &[context.i32_type().const_int(offset, false)],
-            "",
-        )
-    };
+) -> Result<(), CompileError> {
+    let declaration = &decl.declarations[0];
+    let name = &declaration.id.name;
 
+    let value = compile_expr(
+        &declaration.init,
+        env.clone(),
+        context,
+        module,
+        builder,
+        function,
+    )?;
+    let ptr = build_slot_ptr(name, &env, context, builder)?;
     builder.build_store(ptr, value);
 
     Ok(())
 }
 
 pub fn compile_expr_stmt<'ctx>(
-    es_node: &Value,
+    stmt: &ExpressionStatement,
     env: Rc<Env<'ctx>>,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
     function: &FunctionValue<'ctx>,
-) -> Result<PointerValue<'ctx>, Error> {
-    compile_expr(
-        es_node.get("expression").unwrap(),
-        env,
-        context,
-        module,
-        builder,
-        function,
-    )
+) -> Result<IntValue<'ctx>, CompileError> {
+    compile_expr(&stmt.expression, env, context, module, builder, function)
 }
 
 pub fn compile_block_stmt<'ctx>(
-    es_node: &Value,
+    stmt: &BlockStatement,
     parent: Rc<Env<'ctx>>,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
     function: &FunctionValue<'ctx>,
-) -> Result<(), Error> {
-    let body = es_node.get("body").unwrap().as_array().unwrap();
+) -> Result<(), CompileError> {
+    let body = &stmt.body;
     let env = Rc::new(allocate_env(body, Some(parent), context, module, builder)?);
 
     for s in body.iter() {
-        compile_stmt(s, env.clone(), context, module, builder, function).unwrap();
+        compile_stmt(s, env.clone(), context, module, builder, function)?;
 
-        if s.get("type").unwrap().as_str().unwrap() == "ReturnStatement" {
+        if let Stmt::ReturnStatement(_) = s {
             break;
         }
     }
@@ -138,25 +104,22 @@ pub fn compile_block_stmt<'ctx>(
 }
 
 pub fn compile_if_stmt<'ctx>(
-    es_node: &Value,
+    stmt: &IfStatement,
     env: Rc<Env<'ctx>>,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
     function: &FunctionValue<'ctx>,
-) -> Result<(), Error> {
-    let test = es_node.get("test").unwrap();
-    let test_result_ptr = compile_expr(test, env.clone(), context, module, builder, function)?;
-
-    let zero = context.i32_type().const_int(0, false);
-    let one = context.i32_type().const_int(1, false);
-
-    let test_result_value_ptr =
-        unsafe { builder.build_in_bounds_gep(test_result_ptr, &[zero, one], "") };
-    let value = builder
-        .build_load(test_result_value_ptr, "")
-        .into_int_value();
-    let as_i1 = builder.build_int_truncate(value, context.bool_type(), "");
+) -> Result<(), CompileError> {
+    let as_i1 = compile_condition(
+        &stmt.test,
+        stmt.loc,
+        env.clone(),
+        context,
+        module,
+        builder,
+        function,
+    )?;
 
     let consequent_block = context.append_basic_block(*function, "if.true");
     let alternate_block = context.append_basic_block(*function, "if.false");
@@ -166,7 +129,7 @@ pub fn compile_if_stmt<'ctx>(
 
     builder.position_at_end(consequent_block);
     compile_stmt(
-        es_node.get("consequent").unwrap(),
+        &stmt.consequent,
         env.clone(),
         context,
         module,
@@ -183,14 +146,7 @@ pub fn compile_if_stmt<'ctx>(
     }
 
     builder.position_at_end(alternate_block);
-    compile_stmt(
-        es_node.get("alternate").unwrap(),
-        env,
-        context,
-        module,
-        builder,
-        function,
-    )?;
+    compile_stmt(&stmt.alternate, env, context, module, builder, function)?;
     if builder
         .get_insert_block()
         .unwrap()
@@ -206,70 +162,38 @@ pub fn compile_if_stmt<'ctx>(
 }
 
 pub fn compile_fn_decl<'ctx>(
-    es_node: &Value,
+    decl: &FunctionDeclaration,
     env: Rc<Env<'ctx>>,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
-) -> Result<(), Error> {
-    let source_obj_type = module.get_struct_type("source_obj").unwrap();
-    let source_obj_ptr_type = source_obj_type.ptr_type(AddressSpace::Generic);
-    let source_obj_ptr_ptr_type = source_obj_ptr_type.ptr_type(AddressSpace::Generic);
-
-    let name = es_node
-        .get("id")
-        .unwrap()
-        .get("name")
-        .unwrap()
-        .as_str()
-        .unwrap();
+) -> Result<(), CompileError> {
+    let name = decl.id.name.as_str();
     let lit = compile_fn_expr(
         Some(name),
-        es_node,
+        &decl.params,
+        &decl.body,
         env.clone(),
-        false,
         context,
         module,
         builder,
     )?;
 
-    let mut frame = env.ptr.clone().unwrap();
-    let (jumps, offset) = env.lookup(name)?;
-
-    for _ in 0..jumps {
-        let tmp = builder
-            .build_bitcast(*frame, frame.get_type().ptr_type(AddressSpace::Generic), "")
-            .into_pointer_value();
-        frame = Rc::new(builder.build_load(tmp, "").into_pointer_value());
-    }
-
-    let frame_casted = builder
-        .build_bitcast(*frame, source_obj_ptr_ptr_type, "")
-        .into_pointer_value();
-    // SAFETY: Inherently unsafe
-    let ptr = unsafe {
-        builder.build_in_bounds_gep(
-            frame_casted,
-            &[context.i32_type().const_int(offset, false)],
-            "",
-        )
-    };
-
+    let ptr = build_slot_ptr(name, &env, context, builder)?;
     builder.build_store(ptr, lit);
 
     Ok(())
 }
 
 pub fn compile_return_stmt<'ctx>(
-    es_node: &Value,
+    stmt: &ReturnStatement,
     env: Rc<Env<'ctx>>,
     context: &'ctx Context,
     module: &Module<'ctx>,
     builder: &Builder<'ctx>,
     function: &FunctionValue<'ctx>,
-) -> Result<(), Error> {
-    let argument = es_node.get("argument").unwrap();
-    let result = compile_expr(argument, env, context, module, builder, function)?;
+) -> Result<(), CompileError> {
+    let result = compile_tail_expr(&stmt.argument, env, context, module, builder, function)?;
     builder.build_return(Some(&result));
 
     Ok(())
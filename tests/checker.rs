@@ -0,0 +1,246 @@
+use sourcec::{
+    ast::Position, compile, compile_source, interpret_source, CompileError, CompileErrors,
+};
+
+/// Returns the error the checker reports for the program, which must be the
+/// same from the compiler and the interpreter.
+fn check_error(source: &str) -> String {
+    let compiled = compile_source(source).unwrap_err().to_string();
+    let interpreted = interpret_source(source, &mut vec![])
+        .unwrap_err()
+        .to_string();
+    assert_eq!(compiled, interpreted);
+    compiled
+}
+
+fn errors(result: Result<String, CompileErrors>) -> Vec<CompileError> {
+    result.unwrap_err().0
+}
+
+#[test]
+fn undeclared_names() {
+    assert_eq!(check_error("1;\nx;"), "Line 2: Name x not declared.");
+    assert_eq!(
+        check_error("{ const x = 1; }\nx;"),
+        "Line 2: Name x not declared."
+    );
+}
+
+#[test]
+fn duplicate_declarations() {
+    assert_eq!(
+        check_error("const x = 1;\nfunction x() { return 1; }"),
+        "Line 2: Name x has already been declared."
+    );
+    assert_eq!(
+        check_error("function f(x) {\n    const x = 1;\n    return x;\n}"),
+        "Line 2: Name x has already been declared."
+    );
+    assert_eq!(
+        check_error("const f = (x, x) => x;"),
+        "Line 1: Name x has already been declared."
+    );
+}
+
+#[test]
+fn assignments_to_constants() {
+    assert_eq!(
+        check_error("const x = 1;\nx = 2;"),
+        "Line 2: Cannot assign new value to constant x."
+    );
+    assert_eq!(
+        check_error("display = 1;"),
+        "Line 1: Cannot assign new value to constant display."
+    );
+    assert_eq!(
+        check_error("undefined = 1;"),
+        "Line 1: Cannot assign new value to constant undefined."
+    );
+}
+
+#[test]
+fn top_level_returns() {
+    assert_eq!(
+        check_error("if (true) {\n    return 1;\n} else {}"),
+        "Line 2: Cannot return outside of a function."
+    );
+}
+
+#[test]
+fn display_arity() {
+    assert_eq!(
+        check_error("display(1, 2);"),
+        "Line 1: Expected 1 arguments, but got 2."
+    );
+}
+
+#[test]
+fn builtin_functions() {
+    assert_eq!(
+        check_error("pair(1);"),
+        "Line 1: Expected 2 arguments, but got 1."
+    );
+
+    // list takes any number of arguments, and declarations shadow built-ins
+    let source = "
+function head(xs) { return xs; }
+display(head(list()));
+list(1, 2, 3);
+";
+    compile_source(source).unwrap();
+    let mut stdout = vec![];
+    interpret_source(source, &mut stdout).unwrap();
+    assert_eq!(
+        String::from_utf8(stdout).unwrap(),
+        "null\n[1, [2, [3, null]]]\n"
+    );
+}
+
+#[test]
+fn prelude_functions() {
+    assert_eq!(
+        check_error("stringify(1, 2);"),
+        "Line 1: Expected 1 arguments, but got 2."
+    );
+    assert_eq!(
+        check_error("map = 1;"),
+        "Line 1: Cannot assign new value to constant map."
+    );
+
+    // like built-ins, the prelude's functions are values, and top-level
+    // declarations shadow them
+    let source = "
+const f = length;
+function map(f, xs) { return f(xs); }
+display(f(list(1, 2)));
+map(length, list(3));
+";
+    compile_source(source).unwrap();
+    let mut stdout = vec![];
+    interpret_source(source, &mut stdout).unwrap();
+    assert_eq!(String::from_utf8(stdout).unwrap(), "2\n1\n");
+}
+
+#[test]
+fn well_formed_programs_pass() {
+    // names are in scope throughout their block, so functions may be mutually
+    // recursive, and inner declarations may shadow outer ones
+    let source = "
+const x = 1;
+function is_even(n) { return n === 0 ? true : is_odd(n - 1); }
+function is_odd(n) { return n === 0 ? false : is_even(n - 1); }
+function f(y) { const x = y; return x; }
+display(is_even(10));
+f(undefined);
+";
+    compile_source(source).unwrap();
+    let mut stdout = vec![];
+    interpret_source(source, &mut stdout).unwrap();
+    assert_eq!(String::from_utf8(stdout).unwrap(), "true\nundefined\n");
+}
+
+#[test]
+fn errors_carry_their_kind_and_location() {
+    match errors(compile_source("const x = 1;\n{\n    y;\n}")).as_slice() {
+        [CompileError::UndeclaredName {
+            name,
+            loc: Some(loc),
+        }] => {
+            assert_eq!(name, "y");
+            assert_eq!((loc.start.line, loc.start.column), (3, 4));
+        }
+        result => panic!("expected an undeclared name, got {:?}", result),
+    }
+
+    match errors(compile_source("const x = 1")).as_slice() {
+        [CompileError::Syntax {
+            position: Position { line: 1, .. },
+            ..
+        }] => {}
+        result => panic!("expected a syntax error, got {:?}", result),
+    }
+}
+
+#[test]
+fn unsupported_estree_is_pointed_out() {
+    let loc = r#"{ "start": { "line": 2, "column": 0 }, "end": { "line": 2, "column": 9 } }"#;
+    let program = |node: &str| format!(r#"{{ "type": "Program", "body": [{}] }}"#, node);
+
+    let while_loop = program(&format!(
+        r#"{{ "type": "WhileStatement", "test": {{ "type": "Literal", "value": true }}, "body": {{ "type": "BlockStatement", "body": [] }}, "loc": {} }}"#,
+        loc
+    ));
+    match errors(compile(&while_loop)).as_slice() {
+        [CompileError::UnsupportedNode {
+            kind,
+            loc: Some(loc),
+        }] => {
+            assert_eq!(kind, "WhileStatement");
+            assert_eq!(loc.start.line, 2);
+        }
+        result => panic!("expected an unsupported node, got {:?}", result),
+    }
+
+    let type_of = program(&format!(
+        r#"{{ "type": "ExpressionStatement", "expression": {{ "type": "UnaryExpression", "operator": "typeof", "argument": {{ "type": "Literal", "value": 1 }}, "loc": {} }} }}"#,
+        loc
+    ));
+    match errors(compile(&type_of)).as_slice() {
+        [CompileError::UnknownOperator { operator, .. }] => assert_eq!(operator, "typeof"),
+        result => panic!("expected an unknown operator, got {:?}", result),
+    }
+
+    assert!(matches!(
+        errors(compile("{ \"type\": \"Program\" }")).as_slice(),
+        [CompileError::InvalidEstree(_)]
+    ));
+}
+
+#[test]
+fn all_errors_are_reported() {
+    let messages = |result| {
+        errors(result)
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+    };
+
+    // parsing resumes with the statement after a syntax error
+    let source = "
+const x = 1 +;
+function f(n) {
+    const y = ;
+    return n;
+}
+}
+display(x);
+";
+    assert_eq!(
+        messages(compile_source(source)),
+        [
+            "Line 2: SyntaxError: Unexpected token ; (2:13)",
+            "Line 4: SyntaxError: Unexpected token ; (4:14)",
+            "Line 7: SyntaxError: Unexpected token } (7:0)",
+        ]
+    );
+
+    // and so does checking
+    let source = "
+const x = 1;
+const x = 2;
+function f(a) {
+    return b;
+}
+x = 3;
+display(1, 2);
+";
+    assert_eq!(
+        messages(compile_source(source)),
+        [
+            "Line 3: Name x has already been declared.",
+            "Line 5: Name b not declared.",
+            "Line 7: Cannot assign new value to constant x.",
+            "Line 8: Expected 1 arguments, but got 2.",
+        ]
+    );
+}
@@ -0,0 +1,33 @@
+//! Helpers shared by the tests running the programs in `tests/golden`.
+
+use std::{
+    fs::{read_dir, File},
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+};
+
+/// The ESTree JSON files in `tests/golden`, sorted by name.
+pub fn fixtures() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let mut fixtures: Vec<_> = read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().map_or(false, |ext| ext == "json"))
+        .collect();
+    fixtures.sort();
+    fixtures
+}
+
+/// Runs the fixture through `sourcec run` in a child process rather than with
+/// `sourcec::run`. The compiled code displays values with libc's `printf`,
+/// which writes to the stdout of the process rather than anything the test can
+/// capture, and a runtime error calls `exit`, which would end the test run.
+pub fn run_compiled(fixture: &Path) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sourcec"))
+        .arg("run")
+        .stdin(File::open(fixture).unwrap())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .unwrap()
+}
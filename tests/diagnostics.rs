@@ -0,0 +1,122 @@
+use serde_json::{json, Value};
+use sourcec::{
+    compile_source,
+    diagnostics::{render, Diagnostic, ErrorFormat},
+};
+
+const SOURCE: &str = "const y = 1;
+display(x);
+function f(n) {
+    return n;
+}
+y = 2;
+";
+
+fn diagnostics(source: &str) -> Vec<Diagnostic> {
+    compile_source(source)
+        .unwrap_err()
+        .0
+        .iter()
+        .map(Diagnostic::from)
+        .collect()
+}
+
+#[test]
+fn human_format_underlines_snippets() {
+    let rendered = render(
+        &diagnostics(SOURCE),
+        ErrorFormat::Human,
+        "main.js",
+        Some(SOURCE),
+    );
+    assert_eq!(
+        rendered,
+        "\
+error: Name x not declared.
+ --> main.js:2:9
+  |
+2 | display(x);
+  |         ^ not declared
+  |
+  = note: Names are declared with const or function, or as parameters.
+
+error: Cannot assign new value to constant y.
+ --> main.js:6:1
+  |
+1 | const y = 1;
+  |       - declared here
+...
+6 | y = 2;
+  | ^^^^^ assigned here
+  |
+  = note: All names in Source §2 are constant, so declare a new name for the new value instead.
+"
+    );
+}
+
+#[test]
+fn human_format_without_source() {
+    let rendered = render(
+        &diagnostics("const x = 1;\nconst x = 2;"),
+        ErrorFormat::Human,
+        "<stdin>",
+        None,
+    );
+    assert_eq!(
+        rendered,
+        "\
+error: Name x has already been declared.
+ --> <stdin>:2:7
+  = note: first declared here at <stdin>:1:7
+  = note: A block can declare a name only once, and a function's body can't redeclare its parameters.
+"
+    );
+}
+
+#[test]
+fn syntax_errors_point_at_the_token() {
+    let source = "const x = 1 +;";
+    let rendered = render(
+        &diagnostics(source),
+        ErrorFormat::Human,
+        "main.js",
+        Some(source),
+    );
+    assert_eq!(
+        rendered,
+        "\
+error: SyntaxError: Unexpected token ; (1:13)
+ --> main.js:1:14
+  |
+1 | const x = 1 +;
+  |              ^
+"
+    );
+}
+
+#[test]
+fn json_format_has_every_label() {
+    let rendered = render(&diagnostics(SOURCE), ErrorFormat::Json, "main.js", None);
+    let rendered: Value = serde_json::from_str(&rendered).unwrap();
+    let loc = |line, start, end| {
+        json!({
+            "start": { "line": line, "column": start },
+            "end": { "line": line, "column": end },
+        })
+    };
+
+    assert_eq!(rendered.as_array().unwrap().len(), 2);
+    assert_eq!(
+        rendered[1],
+        json!({
+            "severity": "error",
+            "message": "Cannot assign new value to constant y.",
+            "loc": loc(6, 0, 5),
+            "labels": [
+                { "message": "declared here", "primary": false, "loc": loc(1, 6, 7) },
+                { "message": "assigned here", "primary": true, "loc": loc(6, 0, 5) },
+            ],
+            "notes": ["All names in Source §2 are constant, so declare a new name for the new value instead."],
+        })
+    );
+}
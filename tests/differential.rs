@@ -0,0 +1,55 @@
+//! Runs each program in `tests/golden` both through the reference interpreter
+//! and as compiled code, and reports the programs where their stdout or exit
+//! code differ. Unlike `golden.rs`, this needs no expected outputs, so any
+//! program dropped into `tests/golden` is checked against the interpreter.
+
+mod common;
+
+use std::fs::read_to_string;
+
+use common::{fixtures, run_compiled};
+use sourcec::InterpretError;
+
+/// Interprets the program in-process, printing a runtime error and exiting
+/// with code 1 like the compiled code does.
+fn interpret(es_str: &str) -> (String, i32) {
+    let mut stdout = vec![];
+    let code = match sourcec::interpret(es_str, &mut stdout) {
+        Ok(()) => 0,
+        Err(err @ InterpretError::Runtime { .. }) => {
+            stdout.extend(format!("{}\n", err).as_bytes());
+            1
+        }
+        Err(err) => panic!("{}", err),
+    };
+    (String::from_utf8(stdout).unwrap(), code)
+}
+
+#[test]
+fn interpreter_and_compiled_code_agree() {
+    let fixtures = fixtures();
+    assert!(!fixtures.is_empty(), "no fixtures found");
+
+    let mut mismatches = vec![];
+    for fixture in fixtures.iter() {
+        let (expected_stdout, expected_code) = interpret(&read_to_string(fixture).unwrap());
+
+        let output = run_compiled(fixture);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let code = output.status.code();
+
+        if stdout != expected_stdout || code != Some(expected_code) {
+            mismatches.push(format!(
+                "{}: interpreter exited with code {} and stdout\n{}\ncompiled code exited with code {:?} and stdout\n{}\nstderr\n{}",
+                fixture.display(),
+                expected_code,
+                expected_stdout,
+                code,
+                stdout,
+                String::from_utf8_lossy(&output.stderr),
+            ));
+        }
+    }
+
+    assert!(mismatches.is_empty(), "\n{}", mismatches.join("\n"));
+}
@@ -0,0 +1,48 @@
+//! Runs each program in `tests/golden` and compares its stdout and exit code to
+//! the checked-in `.stdout` and `.code` files.
+//!
+//! The programs are given as ESTree JSON so that Node isn't needed, with their
+//! Source next to them. The JSON is acorn's ESTree, parsed with the options
+//! js-slang parses Source with, so it has the fields sourcec ignores like
+//! `start`, `end` and `raw` too. Regenerate a fixture with
+//! `scripts/parse < tests/golden/<name>.js > tests/golden/<name>.json`.
+
+mod common;
+
+use std::fs::read_to_string;
+
+use common::{fixtures, run_compiled};
+
+#[test]
+fn golden_outputs() {
+    let fixtures = fixtures();
+    assert!(!fixtures.is_empty(), "no fixtures found");
+
+    let mut failures = vec![];
+    for fixture in fixtures.iter() {
+        let expected_stdout = read_to_string(fixture.with_extension("stdout")).unwrap();
+        let expected_code: i32 = read_to_string(fixture.with_extension("code"))
+            .unwrap()
+            .trim()
+            .parse()
+            .unwrap();
+
+        let output = run_compiled(fixture);
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let code = output.status.code();
+
+        if stdout != expected_stdout || code != Some(expected_code) {
+            failures.push(format!(
+                "{}: expected exit code {} and stdout\n{}\ngot exit code {:?} and stdout\n{}\nstderr\n{}",
+                fixture.display(),
+                expected_code,
+                expected_stdout,
+                code,
+                stdout,
+                String::from_utf8_lossy(&output.stderr),
+            ));
+        }
+    }
+
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}
@@ -0,0 +1,17 @@
+display(1 + 2);
+display(7 - 10);
+display(6 * 7);
+display(1 / 3);
+display(7 % 3);
+display(-7 % 3);
+display(5.5 % 2);
+display(1 / 0);
+display(-1 / 0);
+display(0 / 0);
+display(-(2 * 3));
+display(0.1 + 0.2);
+display(1e21);
+display(123456789 * 1000000000);
+display(0.000001 / 4);
+display(2 - 2 * 3 + 8 / 4);
+(1 + 2) * 3;
@@ -0,0 +1,26 @@
+display(map(head, list(list(1), list(2, 3))));
+display(map(tail, list(pair(1, 2))));
+display(accumulate(pair, null, list(1, 2)));
+for_each(display, list(3, 4));
+display(filter(is_pair, list(1, pair(2, 3), null)));
+display(map(is_null, list(null, 1)));
+display(map(stringify, list(1, true)));
+
+const make_list = list;
+display(make_list());
+display(make_list(5, 6, 7));
+display(map(list, list(8, 9)));
+
+// set_head and set_tail through a value, in tail position
+function set(setter, p, x) {
+    return setter(p, x);
+}
+const p = pair(1, 2);
+set(set_head, p, 10);
+set(set_tail, p, 20);
+display(p);
+
+display(head === head);
+display(head === tail);
+display(head);
+stringify(list(pair, display));
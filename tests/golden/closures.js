@@ -0,0 +1,38 @@
+function make_adder(n) {
+    return x => x + n;
+}
+const add5 = make_adder(5);
+const add10 = make_adder(10);
+display(add5(1));
+display(add10(1));
+display(add5(add10(0)));
+
+function compose(f, g) {
+    return x => f(g(x));
+}
+const square = x => x * x;
+display(compose(square, add5)(2));
+display(compose(add5, square)(2));
+
+function twice(f) {
+    return x => f(f(x));
+}
+display(twice(twice(add5))(0));
+
+const curried = a => b => c => a + b + c;
+display(curried("a")("b")("c"));
+
+function outer() {
+    const secret = "inner value";
+    function inner() {
+        return secret;
+    }
+    return inner;
+}
+display(outer()());
+display(square);
+display(() => undefined);
+(() => {
+    const x = 1;
+    return x + 1;
+})();
@@ -0,0 +1,26 @@
+display(1 < 2);
+display(2 < 1);
+display(2 > 1);
+display(1 > 1);
+display(1 <= 1);
+display(2 <= 1);
+display(1 >= 1);
+display(0 >= 1);
+display(1 === 1);
+display(1 === 2);
+display(1 !== 2);
+display(0 === -0);
+display(0 / 0 === 0 / 0);
+display(1 === "1");
+display(true === true);
+display(true === false);
+display(undefined === undefined);
+display(undefined === false);
+function f(x) {
+    return x;
+}
+const g = f;
+display(f === g);
+display(f === (x => x));
+display(f !== (x => x));
+1 < 2 === true;
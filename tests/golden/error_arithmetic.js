@@ -0,0 +1,3 @@
+display("before");
+const x = 6;
+x * "two";
@@ -0,0 +1,5 @@
+display("before");
+function f(x) {
+    return x;
+}
+f(1, 2);
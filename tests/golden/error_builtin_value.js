@@ -0,0 +1,2 @@
+display(map(head, list(list(1))));
+map(head, list(1));
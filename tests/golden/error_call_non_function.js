@@ -0,0 +1,3 @@
+display("before");
+const x = "not a function";
+x(1);
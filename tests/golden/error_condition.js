@@ -0,0 +1,9 @@
+display("before");
+function f(x) {
+    if (x) {
+        return 1;
+    } else {
+        return 2;
+    }
+}
+f(0);
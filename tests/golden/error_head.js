@@ -0,0 +1,3 @@
+const xs = list(1);
+display(head(xs));
+tail(tail(xs));
@@ -0,0 +1,2 @@
+display("before");
+true + 1;
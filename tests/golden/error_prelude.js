@@ -0,0 +1,3 @@
+display(length(list(1, 2)));
+// errors in the prelude's functions have no line in the program to report
+length(5);
@@ -0,0 +1,2 @@
+display("before");
+1 + "one";
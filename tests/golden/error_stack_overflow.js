@@ -0,0 +1,10 @@
+// each pending `1 + ...` keeps a call nested, so the recursion fails once calls
+// are nested too deeply, rather than overflowing the native stack
+function count(n) {
+    return n === 0 ? 0 : 1 + count(n - 1);
+}
+
+// the depth is back to zero after each call returns
+display(count(4000));
+display(count(4000));
+count(10000000);
@@ -0,0 +1,2 @@
+display("before");
+undefined ? 1 : 2;
@@ -0,0 +1,21 @@
+// Collections run while these are built, and marking them follows what each
+// object points to without recursing, however deep the chain.
+function nest(n, xs) {
+    return n === 0 ? xs : nest(n - 1, pair(xs, null));
+}
+function depth(xs, d) {
+    return is_null(xs) ? d : depth(head(xs), d + 1);
+}
+const nested = nest(100000, null);
+display(depth(nested, 0));
+
+// each closure's frame refers to the previous closure
+function chain(n, f) {
+    return n === 0 ? f : chain(n - 1, () => f);
+}
+function unchain(f, d) {
+    const g = f();
+    return is_null(g) ? d : unchain(g, d + 1);
+}
+const chained = chain(100000, () => null);
+display(unchain(chained, 0));
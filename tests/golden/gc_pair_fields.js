@@ -0,0 +1,25 @@
+// The list is only reachable from the pair until g takes it out, after which
+// only the value already loaded as f's first argument refers to it, which has
+// to keep it alive through the collections churn causes.
+function make() {
+    return pair(list(1, 2, 3), null);
+}
+const p = make();
+
+function churn(n) {
+    const garbage = list(n, n, n, n);
+    return n === 0 ? garbage : churn(n - 1);
+}
+
+function g() {
+    set_head(p, null);
+    churn(20000);
+    return 4;
+}
+
+function f(xs, y) {
+    return accumulate((x, acc) => x + acc, y, xs);
+}
+
+display(f(head(p), g()));
+display(head(p));
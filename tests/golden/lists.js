@@ -0,0 +1,32 @@
+const xs = list(1, 2, 3);
+display(xs);
+display(pair(1, 2));
+display(list());
+display(null);
+display(list("a", list(true, undefined), pair(null, null)));
+display(head(tail(xs)));
+display(is_pair(xs));
+display(is_pair(null));
+display(is_null(tail(tail(tail(xs)))));
+display(is_null(xs));
+
+const p = pair(1, 2);
+set_head(p, 10);
+set_tail(p, list(3));
+display(p);
+display(p === p);
+display(pair(1, 2) === pair(1, 2));
+display(null === null);
+
+// enough garbage pairs for the collector to run while lists are live
+function build(n, xs) {
+    return n === 0 ? xs : build(n - 1, pair(n, xs));
+}
+function sum(xs, acc) {
+    return is_null(xs) ? acc : sum(tail(xs), acc + head(xs));
+}
+function repeat(k, total) {
+    return k === 0 ? total : repeat(k - 1, total + sum(build(100, null), 0));
+}
+display(repeat(1000, 0));
+list(xs, p);
@@ -0,0 +1,29 @@
+display(true && true);
+display(true && false);
+display(false && true);
+display(false || true);
+display(false || false);
+display(true || false);
+display(!true);
+display(!false);
+display(!(1 < 2));
+display(false && 1);
+display(true || 1);
+display(true && 42);
+display(false || "fallback");
+display(1 < 2 ? "yes" : "no");
+display(1 > 2 ? "yes" : "no");
+display(true ? false ? 1 : 2 : 3);
+function sign(x) {
+    if (x < 0) {
+        return -1;
+    } else if (x > 0) {
+        return 1;
+    } else {
+        return 0;
+    }
+}
+display(sign(-5));
+display(sign(5));
+display(sign(0));
+true && !false;
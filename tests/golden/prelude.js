@@ -0,0 +1,36 @@
+// the list library is in scope without being declared
+const xs = enum_list(1, 5);
+display(map(x => x * x, xs));
+display(filter(x => x % 2 === 0, xs));
+display(accumulate((x, acc) => x + acc, 0, xs));
+display(append(xs, list(6, 7)));
+display(reverse(xs));
+display(length(xs));
+display(list_ref(xs, 2));
+display(member(3, xs));
+display(member(9, xs));
+display(remove(3, list(1, 3, 2, 3)));
+display(remove_all(3, list(1, 3, 2, 3)));
+display(build_list(i => i * 10, 4));
+for_each(x => display(x), list("a", true));
+display(equal(list(1, list(2, "x")), list(1, list(2, "x"))));
+display(equal(list(1, 2), list(1, 3)));
+display(list_to_string(list(1, list("a", null), undefined)));
+display(stringify(pair(1.5, "b")));
+
+// length and reverse are iterative, so they run in constant stack
+display(length(reverse(build_list(i => i, 100000))));
+
+// declarations shadow the prelude's functions, but not in the prelude itself,
+// so map still gets the built-in pair
+{
+    function pair(x, y) {
+        return "shadowed";
+    }
+    function length(xs) {
+        return "shadowed";
+    }
+    display(length(map(x => x + 1, xs)));
+    display(pair(1, 2));
+}
+map(x => x + 1, xs);
@@ -0,0 +1,36 @@
+function fib(n) {
+    return n <= 1 ? n : fib(n - 1) + fib(n - 2);
+}
+display(fib(0));
+display(fib(1));
+display(fib(20));
+
+function factorial(n) {
+    if (n === 0) {
+        return 1;
+    } else {
+        return n * factorial(n - 1);
+    }
+}
+display(factorial(10));
+display(factorial(25));
+
+function is_even(n) {
+    return n === 0 ? true : is_odd(n - 1);
+}
+function is_odd(n) {
+    return n === 0 ? false : is_even(n - 1);
+}
+display(is_even(10));
+display(is_odd(7));
+display(is_even(100001));
+
+function sum(n, acc) {
+    return n === 0 ? acc : sum(n - 1, acc + n);
+}
+display(sum(100000, 0));
+
+function repeat(s, n) {
+    return n === 0 ? "" : s + repeat(s, n - 1);
+}
+repeat("ab", 3);
@@ -0,0 +1,8 @@
+function pair(a, b) {
+    return a + b;
+}
+display(pair(1, 2));
+function f(list) {
+    return list(1);
+}
+f(x => x + 1);
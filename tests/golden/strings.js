@@ -0,0 +1,12 @@
+const greeting = "Hello, " + "world";
+display(greeting);
+display("" + "");
+display("abc" + "def" + "ghi");
+display("apple" < "banana");
+display("apple" > "banana");
+display("apple" <= "apple");
+display("b" >= "abc");
+display("abc" === "ab" + "c");
+display("abc" !== "abc");
+display('single quotes');
+greeting;
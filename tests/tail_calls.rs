@@ -0,0 +1,37 @@
+use sourcec::{compile_source, run_source};
+
+const COUNT_UP: &str = "
+function count(n, acc) {
+    return n === 0 ? acc : count(n - 1, acc + 1);
+}
+count(1000000, 0);
+";
+
+/// Returns the body of the function with the given name in the IR.
+fn function_body<'a>(ir: &'a str, name: &str) -> &'a str {
+    let start = ir
+        .find(&format!("@{}(", name))
+        .unwrap_or_else(|| panic!("function {} not found", name));
+    let end = start + ir[start..].find("\n}\n").unwrap();
+    &ir[start..end]
+}
+
+#[test]
+fn tail_calls_are_returned_to_the_trampoline() {
+    let ir = compile_source(COUNT_UP).unwrap();
+
+    // The only call a tail-recursive function makes is through the trampoline
+    // of its caller, so its own body must not call any closure.
+    let count = function_body(&ir, "__count");
+    assert!(!count.contains("call i64 %"), "{}", count);
+    assert!(!count.contains("@trampoline"), "{}", count);
+
+    let main = function_body(&ir, "main");
+    assert!(main.contains("@trampoline"), "{}", main);
+}
+
+#[test]
+fn tail_calls_run_in_constant_stack() {
+    // A million nested calls would overflow the stack without the trampoline.
+    assert_eq!(run_source(COUNT_UP, 0).unwrap(), 0);
+}